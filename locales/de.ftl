@@ -0,0 +1,40 @@
+# Replies of built-in commands, `$prefix` is channel's command prefix
+
+hello = Hallo, { $name }!
+
+never-seen = { $login } wurde hier noch nie gesehen
+first-message = Erste Nachricht: { $line }
+first-message-usage = Verwendung: { $prefix }firstmessage <Nutzer>
+last-seen = Zuletzt gesehen: { $line }
+last-seen-usage = Verwendung: { $prefix }lastseen <Nutzer>
+
+log-page = Seite { $page }: { $lines }
+logs-usage = Verwendung: { $prefix }logs <Nutzer> [Seite]
+logs-empty = Keine Nachrichten von { $login } gefunden (Seite { $page })
+search-usage = Verwendung: { $prefix }search <Text> [#Seite]
+search-empty = Nichts gefunden für '{ $query }' (Seite { $page })
+
+forget-me-confirm = { $name }, damit werden alle deine gespeicherten Nachrichten in diesem Kanal gelöscht. Schreibe { $prefix }forgetme confirm, um fortzufahren
+forget-me-deleted = { $name }, { $count ->
+    [one] { $count } deiner gespeicherten Nachrichten wurde gelöscht
+   *[other] { $count } deiner gespeicherten Nachrichten wurden gelöscht
+}
+
+time-current = Aktuelles Datum und Uhrzeit: { $time }
+time-of-chatter = Aktuelles Datum und Uhrzeit bei { $login }: { $time }
+time-unknown = Unbekannte Zeitzone oder Stadt, oder { $login } hat keine Zeitzone gespeichert
+
+set-timezone-usage = Verwendung: { $prefix }settz <Zone|Stadt|reset>
+set-timezone-unknown = { $name }, unbekannte Zeitzone oder Stadt '{ $query }', versuche z. B. Europe/Berlin
+set-timezone-set = { $name }, deine Zeitzone ist jetzt { $timezone }
+set-timezone-removed = { $name }, deine Zeitzone wurde entfernt
+
+set-language-usage = Verwendung: { $prefix }setlang <{ $locales }|reset>
+set-language-unknown = { $name }, unbekannte Sprache '{ $query }', verfügbar: { $locales }
+set-language-set = { $name }, ich antworte dir ab jetzt auf Deutsch
+set-language-removed = { $name }, ich antworte dir ab jetzt in der Sprache des Kanals
+
+status = Bots: { $bots }
+status-starting = { $channel } startet
+status-running = { $channel } läuft seit { $since }
+status-restarting = { $channel } ist { $attempt }-mal in Folge ausgefallen, Neustart in { $seconds }s: { $error }
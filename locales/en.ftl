@@ -0,0 +1,40 @@
+# Replies of built-in commands, `$prefix` is channel's command prefix
+
+hello = Hello, { $name }!
+
+never-seen = { $login } has never been seen here
+first-message = First message: { $line }
+first-message-usage = Usage: { $prefix }firstmessage <user>
+last-seen = Last seen: { $line }
+last-seen-usage = Usage: { $prefix }lastseen <user>
+
+log-page = Page { $page }: { $lines }
+logs-usage = Usage: { $prefix }logs <user> [page]
+logs-empty = No messages from { $login } found (page { $page })
+search-usage = Usage: { $prefix }search <text> [#page]
+search-empty = Nothing found for '{ $query }' (page { $page })
+
+forget-me-confirm = { $name }, this will delete all of your logged messages in this channel. Type { $prefix }forgetme confirm to proceed
+forget-me-deleted = { $name }, { $count ->
+    [one] deleted your { $count } logged message
+   *[other] deleted all { $count } of your logged messages
+}
+
+time-current = Current datetime: { $time }
+time-of-chatter = Current datetime of { $login }: { $time }
+time-unknown = Unknown timezone or city, or { $login } hasn't saved their timezone
+
+set-timezone-usage = Usage: { $prefix }settz <zone|city|reset>
+set-timezone-unknown = { $name }, unknown timezone or city '{ $query }', try e.g. Europe/Warsaw
+set-timezone-set = { $name }, your timezone is set to { $timezone }
+set-timezone-removed = { $name }, your timezone is removed
+
+set-language-usage = Usage: { $prefix }setlang <{ $locales }|reset>
+set-language-unknown = { $name }, unknown language '{ $query }', available: { $locales }
+set-language-set = { $name }, I'll answer you in English
+set-language-removed = { $name }, I'll answer you in channel's language
+
+status = Bots: { $bots }
+status-starting = { $channel } is starting
+status-running = { $channel } is running since { $since }
+status-restarting = { $channel } failed { $attempt } times in a row, restarting in { $seconds }s: { $error }
@@ -0,0 +1,41 @@
+# Replies of built-in commands, `$prefix` is channel's command prefix
+
+hello = Cześć, { $name }!
+
+never-seen = { $login } nigdy tu nie pisał(a)
+first-message = Pierwsza wiadomość: { $line }
+first-message-usage = Użycie: { $prefix }firstmessage <użytkownik>
+last-seen = Ostatnio widziany(a): { $line }
+last-seen-usage = Użycie: { $prefix }lastseen <użytkownik>
+
+log-page = Strona { $page }: { $lines }
+logs-usage = Użycie: { $prefix }logs <użytkownik> [strona]
+logs-empty = Brak wiadomości od { $login } (strona { $page })
+search-usage = Użycie: { $prefix }search <tekst> [#strona]
+search-empty = Nic nie znaleziono dla '{ $query }' (strona { $page })
+
+forget-me-confirm = { $name }, to usunie wszystkie twoje zapisane wiadomości na tym kanale. Wpisz { $prefix }forgetme confirm, aby kontynuować
+forget-me-deleted = { $name }, usunięto { $count ->
+    [one] { $count } twoją zapisaną wiadomość
+    [few] { $count } twoje zapisane wiadomości
+   *[other] { $count } twoich zapisanych wiadomości
+}
+
+time-current = Aktualna data i godzina: { $time }
+time-of-chatter = Aktualna data i godzina u { $login }: { $time }
+time-unknown = Nieznana strefa czasowa lub miasto, albo { $login } nie zapisał(a) swojej strefy czasowej
+
+set-timezone-usage = Użycie: { $prefix }settz <strefa|miasto|reset>
+set-timezone-unknown = { $name }, nieznana strefa czasowa lub miasto '{ $query }', spróbuj np. Europe/Warsaw
+set-timezone-set = { $name }, twoja strefa czasowa to teraz { $timezone }
+set-timezone-removed = { $name }, twoja strefa czasowa została usunięta
+
+set-language-usage = Użycie: { $prefix }setlang <{ $locales }|reset>
+set-language-unknown = { $name }, nieznany język '{ $query }', dostępne: { $locales }
+set-language-set = { $name }, będę ci odpowiadać po polsku
+set-language-removed = { $name }, będę ci odpowiadać w języku kanału
+
+status = Boty: { $bots }
+status-starting = { $channel } startuje
+status-running = { $channel } działa od { $since }
+status-restarting = { $channel } padł { $attempt } razy z rzędu, restart za { $seconds }s: { $error }
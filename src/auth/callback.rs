@@ -0,0 +1,258 @@
+//! OAuth redirect endpoint, receives authorization code once the user logs in
+
+use std::convert::Infallible;
+use std::fmt::{Display, Formatter};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use hyper::{Body, Request, Response, Server, StatusCode};
+use hyper::server::conn::AddrIncoming;
+use hyper::service::{make_service_fn, service_fn};
+use tokio::sync::{mpsc, oneshot};
+
+/// Path Twitch redirects to, has to match the redirect URL registered for the app
+pub const CALLBACK_PATH: &str = "/oauth-receive/";
+pub const DEFAULT_CALLBACK_TIMEOUT_SEC: u64 = 300;
+
+#[derive(Debug, PartialEq)]
+pub enum CallbackError {
+    /// Twitch redirected back with an error, e.g. the user declined authorization
+    Denied {
+        error: String,
+        description: Option<String>,
+    },
+    MissingParameter(&'static str),
+    /// Returned state doesn't match the one we generated, request didn't originate from our authorize URL
+    StateMismatch,
+}
+
+impl CallbackError {
+    /// Whether the flow can't complete anymore, other errors might be followed by a valid request
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, CallbackError::Denied { .. })
+    }
+}
+
+impl Display for CallbackError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallbackError::Denied { error, description } => {
+                write!(f, "Authorization failed: {}", description.as_deref().unwrap_or(error.as_str()))
+            },
+            CallbackError::MissingParameter(name) => write!(f, "Couldn't find query parameter '{}'", name),
+            CallbackError::StateMismatch => write!(f, "State doesn't match, please start the login again"),
+        }
+    }
+}
+
+impl std::error::Error for CallbackError {}
+
+/// Compares strings in constant time, so the state can't be guessed by timing the responses
+fn secure_eq(left: &str, right: &str) -> bool {
+    left.len() == right.len() && left.bytes().zip(right.bytes()).fold(0_u8, |acc, (l, r)| acc | (l ^ r)) == 0
+}
+
+/// Extracts authorization code from redirect's query, making sure it belongs to our authorization request
+pub fn validate_callback(query: &str, expected_state: &str) -> Result<String, CallbackError> {
+    let mut code: Option<String> = Option::None;
+    let mut state: Option<String> = Option::None;
+    let mut error: Option<String> = Option::None;
+    let mut error_description: Option<String> = Option::None;
+
+    for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+        match key.as_ref() {
+            "code" => code = Option::Some(value.into_owned()),
+            "state" => state = Option::Some(value.into_owned()),
+            "error" => error = Option::Some(value.into_owned()),
+            "error_description" => error_description = Option::Some(value.into_owned()),
+            _ => {},
+        }
+    }
+
+    let state = state.ok_or(CallbackError::MissingParameter("state"))?;
+
+    if !secure_eq(state.as_str(), expected_state) {
+        return Err(CallbackError::StateMismatch);
+    }
+
+    if let Some(error) = error {
+        return Err(CallbackError::Denied {
+            error,
+            description: error_description,
+        });
+    }
+
+    code.ok_or(CallbackError::MissingParameter("code"))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+pub fn render_page(title: &str, message: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>develbot - {title}</title></head>\n\
+        <body style=\"font-family: sans-serif; text-align: center; margin-top: 4em\">\n<h1>{title}</h1>\n<p>{message}</p>\n</body>\n</html>\n",
+        title = escape_html(title),
+        message = escape_html(message),
+    )
+}
+
+fn html_response(status: StatusCode, title: &str, message: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "text/html; charset=utf-8")
+        .body(Body::from(render_page(title, message)))
+        .unwrap() // Safe unwrap, status and header are valid
+}
+
+type CallbackResult = Result<String, CallbackError>;
+
+/// Bound callback endpoint, serves until a valid redirect arrives, the flow fails or times out
+pub struct CallbackServer {
+    local_addr: SocketAddr,
+    results: mpsc::UnboundedReceiver<CallbackResult>,
+    shutdown: oneshot::Sender<()>,
+    server: tokio::task::JoinHandle<Result<(), hyper::Error>>,
+}
+
+impl CallbackServer {
+    pub fn bind(address: &SocketAddr, expected_state: String) -> anyhow::Result<Self> {
+        let (sender, results) = mpsc::unbounded_channel::<CallbackResult>();
+        let (shutdown, shutdown_receiver) = oneshot::channel::<()>();
+
+        let make_service = make_service_fn(move |_| {
+            let sender = sender.clone();
+            let expected_state = expected_state.clone();
+
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let response = CallbackServer::handle(&request, expected_state.as_str(), &sender);
+
+                    async move { Ok::<_, Infallible>(response) }
+                }))
+            }
+        });
+
+        let incoming = AddrIncoming::bind(address)
+            .map_err(|error| anyhow::anyhow!("Failed to setup oauth callback server on {}: {}", address, error))?;
+        let local_addr = incoming.local_addr();
+
+        let server = Server::builder(incoming)
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                shutdown_receiver.await.unwrap_or(());
+            });
+
+        Ok(Self {
+            local_addr,
+            results,
+            shutdown,
+            server: tokio::spawn(server),
+        })
+    }
+
+    fn handle(request: &Request<Body>, expected_state: &str, sender: &mpsc::UnboundedSender<CallbackResult>) -> Response<Body> {
+        if request.uri().path() != CALLBACK_PATH {
+            return html_response(StatusCode::NOT_FOUND, "Not found", "There's nothing here.");
+        }
+
+        match validate_callback(request.uri().query().unwrap_or(""), expected_state) {
+            Ok(code) => {
+                sender.send(Ok(code)).unwrap_or(());
+
+                html_response(StatusCode::OK, "Logged in", "Got the token, good to go. You can close this page now.")
+            },
+            Err(error) => {
+                log::warn!("Rejected oauth callback: {}", error);
+
+                let status = if error.is_terminal() { StatusCode::UNAUTHORIZED } else { StatusCode::BAD_REQUEST };
+                let page = html_response(status, "Login failed", error.to_string().as_str());
+
+                if error.is_terminal() {
+                    sender.send(Err(error)).unwrap_or(());
+                }
+
+                page
+            },
+        }
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Waits for the authorization code and shuts the endpoint down
+    pub async fn wait(mut self, timeout: Duration) -> anyhow::Result<String> {
+        let result = tokio::time::timeout(timeout, self.results.recv()).await;
+
+        self.shutdown.send(()).unwrap_or(());
+        self.server.await??;
+
+        log::debug!("Shut down oauth callback endpoint");
+
+        match result {
+            Ok(Some(result)) => Ok(result?),
+            Ok(None) => Err(anyhow::anyhow!("OAuth callback endpoint stopped unexpectedly")),
+            Err(_) => Err(anyhow::anyhow!("Timed out after {}s waiting for the login", timeout.as_secs())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{CallbackError, CallbackServer, render_page, validate_callback};
+
+    #[test]
+    fn validate_callback_works() {
+        assert_eq!(validate_callback("code=abc&scope=chat%3Aread&state=xyz", "xyz"), Ok("abc".to_string()));
+        assert_eq!(validate_callback("code=abc&state=forged", "xyz"), Err(CallbackError::StateMismatch));
+        assert_eq!(validate_callback("code=abc", "xyz"), Err(CallbackError::MissingParameter("state")));
+        assert_eq!(validate_callback("state=xyz", "xyz"), Err(CallbackError::MissingParameter("code")));
+
+        let denied = validate_callback("error=access_denied&error_description=The+user+denied+you+access&state=xyz", "xyz");
+        assert_eq!(denied, Err(CallbackError::Denied {
+            error: "access_denied".to_string(),
+            description: Option::Some("The user denied you access".to_string()),
+        }));
+        assert!(denied.unwrap_err().is_terminal());
+
+        // Errors with unknown state aren't trusted to abort the flow
+        assert_eq!(validate_callback("error=access_denied", "xyz"), Err(CallbackError::MissingParameter("state")));
+    }
+
+    #[test]
+    fn render_page_escapes_html() {
+        assert!(render_page("Login failed", "<script>").contains("<p>&lt;script&gt;</p>"));
+    }
+
+    #[tokio::test]
+    async fn server_ignores_forged_requests_until_valid_one() {
+        let server = CallbackServer::bind(&([127, 0, 0, 1], 0).into(), "xyz".to_string()).unwrap();
+        let base_url = format!("http://{}/oauth-receive/", server.local_addr());
+
+        let waiting = tokio::spawn(server.wait(Duration::from_secs(10)));
+
+        let forged = reqwest::get(format!("{}?code=evil&state=forged", base_url).as_str()).await.unwrap();
+        assert_eq!(forged.status().as_u16(), 400);
+
+        let valid = reqwest::get(format!("{}?code=abc&state=xyz", base_url).as_str()).await.unwrap();
+        assert_eq!(valid.status().as_u16(), 200);
+        assert!(valid.text().await.unwrap().contains("good to go"));
+
+        assert_eq!(waiting.await.unwrap().unwrap(), "abc");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn server_times_out() {
+        let server = CallbackServer::bind(&([127, 0, 0, 1], 0).into(), "xyz".to_string()).unwrap();
+
+        assert!(server.wait(Duration::from_secs(60)).await.is_err());
+    }
+}
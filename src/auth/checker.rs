@@ -0,0 +1,200 @@
+use std::future::Future;
+use std::time::Duration;
+
+use chrono::prelude::*;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+const BACKOFF_INITIAL_SEC: u64 = 2;
+const BACKOFF_MAX_SEC: u64 = 300;
+
+/// State of the periodic token checks, as seen by the rest of the bot
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenHealth {
+    /// First check hasn't completed yet
+    Starting,
+    Healthy {
+        last_checked_at: DateTime<Utc>,
+    },
+    /// Checks keep failing, retried with exponential backoff
+    Failing {
+        since: DateTime<Utc>,
+        attempts: u32,
+        last_error: String,
+    },
+    Stopped,
+}
+
+impl TokenHealth {
+    #[allow(dead_code)]
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, TokenHealth::Healthy { .. })
+    }
+}
+
+/// Exponentially growing delay between retries of failed checks
+#[derive(Debug)]
+pub struct Backoff {
+    attempts: u32,
+    initial: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            attempts: 0,
+            initial,
+            max,
+        }
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let factor = 2_u32.saturating_pow(self.attempts);
+        self.attempts = self.attempts.saturating_add(1);
+
+        self.initial.checked_mul(factor).map_or(self.max, |delay| delay.min(self.max))
+    }
+
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+
+    /// Delays handed out since the last reset
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new(Duration::from_secs(BACKOFF_INITIAL_SEC), Duration::from_secs(BACKOFF_MAX_SEC))
+    }
+}
+
+/// Runs `check` every `period` until cancelled, retrying failed checks with backoff and reporting health
+pub async fn run_checker<F, Fut>(period: Duration, health: &watch::Sender<TokenHealth>, cancellation_token: CancellationToken, mut check: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let mut backoff = Backoff::default();
+    let mut failing_since: Option<DateTime<Utc>> = Option::None;
+    let mut failed_attempts = 0_u32;
+
+    loop {
+        let delay = tokio::select! {
+            _ = cancellation_token.cancelled() => break,
+            result = check() => match result {
+                Ok(()) => {
+                    if failing_since.take().is_some() {
+                        log::info!("Token checks recovered after {} failed attempts", failed_attempts);
+                    }
+
+                    failed_attempts = 0;
+                    backoff.reset();
+
+                    let _ = health.send(TokenHealth::Healthy { last_checked_at: Utc::now() });
+
+                    period
+                },
+                Err(error) => {
+                    let since = *failing_since.get_or_insert_with(Utc::now);
+                    failed_attempts += 1;
+
+                    let delay = backoff.next_delay();
+                    log::error!("Token check failed (attempt {}), retrying in {}s: {}", failed_attempts, delay.as_secs(), error);
+
+                    let _ = health.send(TokenHealth::Failing {
+                        since,
+                        attempts: failed_attempts,
+                        last_error: error.to_string(),
+                    });
+
+                    delay
+                },
+            },
+        };
+
+        tokio::select! {
+            _ = cancellation_token.cancelled() => break,
+            _ = tokio::time::sleep(delay) => {},
+        }
+    }
+
+    let _ = health.send(TokenHealth::Stopped);
+
+    log::debug!("Token checker stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use tokio::sync::watch;
+    use tokio::time::Instant;
+    use tokio_util::sync::CancellationToken;
+
+    use super::{Backoff, run_checker, TokenHealth};
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_max() {
+        let mut backoff = Backoff::new(Duration::from_secs(2), Duration::from_secs(20));
+
+        let delays: Vec<u64> = (0..5).map(|_| backoff.next_delay().as_secs()).collect();
+        assert_eq!(delays, vec![2, 4, 8, 16, 20]);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay().as_secs(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn checker_backs_off_on_errors_and_recovers() {
+        let (health_sender, health) = watch::channel(TokenHealth::Starting);
+        let cancellation_token = CancellationToken::new();
+        let checked_at: Arc<Mutex<Vec<Instant>>> = Arc::new(Mutex::new(vec![]));
+
+        let handle = tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            let checked_at = checked_at.clone();
+
+            async move {
+                run_checker(Duration::from_secs(60), &health_sender, cancellation_token, move || {
+                    let checked_at = checked_at.clone();
+
+                    async move {
+                        let mut checked_at = checked_at.lock().unwrap();
+                        checked_at.push(Instant::now());
+
+                        if checked_at.len() <= 3 {
+                            Err(anyhow::anyhow!("OAuth server is down"))
+                        } else {
+                            Ok(())
+                        }
+                    }
+                }).await
+            }
+        });
+
+        let started_at = Instant::now();
+
+        tokio::time::sleep(Duration::from_secs(7)).await;
+        assert!(matches!(*health.borrow(), TokenHealth::Failing { attempts: 3, .. }));
+
+        tokio::time::sleep(Duration::from_secs(100)).await;
+        assert!(health.borrow().is_healthy());
+
+        cancellation_token.cancel();
+        handle.await.unwrap();
+        assert_eq!(*health.borrow(), TokenHealth::Stopped);
+
+        let offsets: Vec<u64> = checked_at.lock().unwrap()
+            .iter()
+            .map(|instant| instant.duration_since(started_at).as_secs())
+            .collect();
+
+        // Retries after 2, 4 and 8 seconds, then back to the regular period
+        assert_eq!(offsets, vec![0, 2, 6, 14, 74]);
+    }
+}
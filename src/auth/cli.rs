@@ -0,0 +1,359 @@
+//! `auth` subcommand, lets operators manage stored tokens without running the bot
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::ArgMatches;
+use tokio::sync::RwLock;
+use twitch_oauth2::{AccessToken, ClientId};
+use twitch_oauth2::tokens::errors::{RevokeTokenError, ValidationError};
+use twitch_oauth2::ValidatedToken;
+
+use crate::auth::http_client::{oauth_http_client, OAuthHttpError};
+use crate::auth::token_store::StoredToken;
+use crate::auth::TokenClient;
+
+/// Whose token a command is about
+#[derive(Clone, Debug, PartialEq)]
+pub enum Account {
+    Bot,
+    /// Additional identity's login
+    Identity(String),
+    /// Channel login
+    Broadcaster(String),
+}
+
+impl FromStr for Account {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.split_once(':') {
+            None if value == "bot" => Ok(Account::Bot),
+            Some(("bot", login)) if !login.is_empty() => Ok(Account::Identity(login.to_lowercase())),
+            Some(("broadcaster", channel)) if !channel.is_empty() => Ok(Account::Broadcaster(channel.to_lowercase())),
+            _ => Err(anyhow::anyhow!("Unknown account '{}', expected 'bot', 'bot:<identity>' or 'broadcaster:<channel>'", value)),
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}m", minutes),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
+
+fn format_status_line(name: &str, stored_token: &StoredToken, validation: &Result<ValidatedToken, ValidationError<OAuthHttpError>>) -> String {
+    match validation {
+        Ok(validated_token) => {
+            let scopes = validated_token.scopes.as_ref()
+                .map(|scopes| scopes.iter().map(|scope| scope.to_string()).collect::<Vec<String>>().join(", "))
+                .filter(|scopes| !scopes.is_empty())
+                .unwrap_or_else(|| "-".to_string());
+
+            format!(
+                "{:<24} {:<24} expires in {:<10} scopes: {}",
+                name,
+                validated_token.login.as_deref().unwrap_or("-"),
+                format_duration(validated_token.expires_in),
+                scopes
+            )
+        },
+        Err(ValidationError::NotAuthorized) if stored_token.refresh_token.is_some() => {
+            format!("{:<24} expired, going to be refreshed on the next start", name)
+        },
+        Err(ValidationError::NotAuthorized) => format!("{:<24} expired, has to login again", name),
+        Err(error) => format!("{:<24} couldn't validate: {}", name, error),
+    }
+}
+
+fn account_name(account: &Account) -> String {
+    match account {
+        Account::Bot => "bot".to_string(),
+        Account::Identity(login) => format!("bot:{}", login),
+        Account::Broadcaster(channel) => format!("broadcaster:{}", channel),
+    }
+}
+
+async fn login(token_client: Arc<RwLock<TokenClient>>, account: Account) -> anyhow::Result<()> {
+    match account {
+        Account::Bot => {
+            let mut token_client = token_client.write().await;
+            let config = token_client.config.clone();
+
+            TokenClient::authorize_bot(&mut token_client, &*config.read().await).await?;
+        },
+        Account::Identity(login) => {
+            let mut token_client = token_client.write().await;
+            let config = token_client.config.clone();
+            let config = config.read().await;
+
+            if !config.app_config.identity_names().contains(&login) {
+                return Err(anyhow::anyhow!("Identity '{}' isn't configured in twitch.identities", login));
+            }
+
+            TokenClient::authorize_identity(&mut token_client, &config, login.as_str()).await?;
+        },
+        Account::Broadcaster(channel) => {
+            let is_known_channel = {
+                let token_client = token_client.read().await;
+                let config = token_client.config.read().await;
+
+                config.app_config.channels.iter().any(|channel_info| channel_info.channel.eq_ignore_ascii_case(channel.as_str()))
+            };
+
+            if !is_known_channel {
+                return Err(anyhow::anyhow!("Channel '{}' isn't configured", channel));
+            }
+
+            TokenClient::authorize_broadcaster(token_client, channel.as_str()).await?;
+        },
+    }
+
+    println!("Logged in, token is stored");
+
+    Ok(())
+}
+
+async fn status(token_client: Arc<RwLock<TokenClient>>) -> anyhow::Result<()> {
+    let token_client = token_client.read().await;
+    let tokens = token_client.token_store.tokens().clone();
+
+    let mut stored_tokens: Vec<(String, StoredToken)> = vec![];
+    stored_tokens.extend(tokens.app.map(|token| ("app".to_string(), token)));
+    stored_tokens.extend(tokens.user.map(|token| (account_name(&Account::Bot), token)));
+    stored_tokens.extend(tokens.identities.into_iter().map(|(login, token)| (account_name(&Account::Identity(login)), token)));
+    stored_tokens.extend(tokens.broadcasters.into_iter().map(|(channel, token)| (account_name(&Account::Broadcaster(channel)), token)));
+
+    if stored_tokens.is_empty() {
+        println!("No tokens stored, run `auth login` first");
+        return Ok(());
+    }
+
+    for (name, stored_token) in stored_tokens {
+        let access_token = AccessToken::new(stored_token.access_token.clone());
+        let validation = twitch_oauth2::validate_token(oauth_http_client(token_client.oauth_base_url.clone()), &access_token).await;
+
+        println!("{}", format_status_line(name.as_str(), &stored_token, &validation));
+    }
+
+    Ok(())
+}
+
+/// Whether Twitch refused to revoke the token only because it's invalid already, e.g. expired or revoked before
+fn is_already_invalid(error: &RevokeTokenError<OAuthHttpError>) -> bool {
+    match error {
+        RevokeTokenError::TwitchError(response) => response.message.to_lowercase().contains("invalid token"),
+        _ => false,
+    }
+}
+
+/// Revokes the token at Twitch, fails unless Twitch revoked it or it's invalid already, so it's safe to forget it
+async fn revoke_stored(token_client: &TokenClient, name: &str, stored_token: Option<&StoredToken>) -> anyhow::Result<()> {
+    let stored_token = stored_token.ok_or_else(|| anyhow::anyhow!("No {} token stored", name))?;
+    let client_id = ClientId::new(token_client.config.read().await.app_config.twitch.client_id.clone());
+    let access_token = AccessToken::new(stored_token.access_token.clone());
+
+    match twitch_oauth2::revoke_token(oauth_http_client(token_client.oauth_base_url.clone()), &access_token, &client_id).await {
+        Ok(()) => println!("Revoked {} token", name),
+        Err(error) if is_already_invalid(&error) => println!("{} token was invalid already, removing it", name),
+        Err(error) => return Err(anyhow::anyhow!("Twitch didn't revoke {} token, it's kept: {}", name, error)),
+    }
+
+    Ok(())
+}
+
+/// Revokes given account's token, every stored token if there's no account
+async fn revoke(token_client: Arc<RwLock<TokenClient>>, account: Option<Account>) -> anyhow::Result<()> {
+    let mut token_client = token_client.write().await;
+    let tokens = token_client.token_store.tokens().clone();
+
+    match account {
+        Some(Account::Bot) => {
+            revoke_stored(&token_client, "bot", tokens.user.as_ref()).await?;
+            token_client.token_store.remove_user_token().await?;
+        },
+        Some(Account::Identity(login)) => {
+            let name = account_name(&Account::Identity(login.clone()));
+
+            revoke_stored(&token_client, name.as_str(), tokens.identities.get(login.as_str())).await?;
+            token_client.token_store.set_identity_token(login.as_str(), Option::None).await?;
+        },
+        Some(Account::Broadcaster(channel)) => {
+            let name = account_name(&Account::Broadcaster(channel.clone()));
+
+            revoke_stored(&token_client, name.as_str(), tokens.broadcasters.get(channel.as_str())).await?;
+            token_client.token_store.set_broadcaster_token(channel.as_str(), Option::None).await?;
+        },
+        None => {
+            // Tokens Twitch didn't revoke are kept, the rest are still revoked
+            let mut errors: Vec<String> = vec![];
+
+            if let Some(app_token) = tokens.app.as_ref() {
+                match revoke_stored(&token_client, "app", Option::Some(app_token)).await {
+                    Ok(()) => token_client.token_store.remove_app_token().await?,
+                    Err(error) => errors.push(error.to_string()),
+                }
+            }
+
+            if let Some(user_token) = tokens.user.as_ref() {
+                match revoke_stored(&token_client, "bot", Option::Some(user_token)).await {
+                    Ok(()) => token_client.token_store.remove_user_token().await?,
+                    Err(error) => errors.push(error.to_string()),
+                }
+            }
+
+            for (login, identity_token) in tokens.identities.iter() {
+                let name = account_name(&Account::Identity(login.clone()));
+
+                match revoke_stored(&token_client, name.as_str(), Option::Some(identity_token)).await {
+                    Ok(()) => token_client.token_store.set_identity_token(login.as_str(), Option::None).await?,
+                    Err(error) => errors.push(error.to_string()),
+                }
+            }
+
+            for (channel, broadcaster_token) in tokens.broadcasters.iter() {
+                let name = account_name(&Account::Broadcaster(channel.clone()));
+
+                match revoke_stored(&token_client, name.as_str(), Option::Some(broadcaster_token)).await {
+                    Ok(()) => token_client.token_store.set_broadcaster_token(channel.as_str(), Option::None).await?,
+                    Err(error) => errors.push(error.to_string()),
+                }
+            }
+
+            if !errors.is_empty() {
+                return Err(anyhow::anyhow!(errors.join("\n")));
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Entry point of `auth` subcommand
+pub async fn run_auth(token_client: Arc<RwLock<TokenClient>>, args: &ArgMatches<'static>) -> anyhow::Result<()> {
+    match args.subcommand() {
+        ("login", Some(login_args)) => {
+            let account = Account::from_str(login_args.value_of("account").unwrap())?; // Safe unwrap, arg has default value
+
+            login(token_client, account).await
+        },
+        ("status", _) => status(token_client).await,
+        ("revoke", Some(revoke_args)) => {
+            let account = revoke_args.value_of("account").map(Account::from_str).transpose()?;
+
+            revoke(token_client, account).await
+        },
+        _ => Err(anyhow::anyhow!("Expected one of subcommands: login, status, revoke")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::sync::RwLock;
+    use twitch_oauth2::{AccessToken, ClientId, Scope, UserToken, ValidatedToken};
+    use twitch_oauth2::tokens::errors::ValidationError;
+
+    use crate::auth::mock_oauth::MockOAuthServer;
+    use crate::auth::token_store::{StoredToken, TokenStore};
+    use crate::auth::TokenClient;
+    use crate::config::Config;
+    use crate::database::repository::memory::MemoryTokenRepository;
+
+    use super::{Account, format_duration, format_status_line, revoke};
+
+    const CONFIG: &str = r#"
+        channels = []
+
+        [global]
+        auth_host = 'localhost'
+        auth_port = 8099
+
+        [database]
+        backend = 'memory'
+
+        [twitch]
+        bot_name = 'develbot'
+        client_id = 'client-id'
+        client_secret = 'client-secret'
+    "#;
+
+    #[test]
+    fn account_parsing_works() {
+        assert_eq!(Account::from_str("bot").unwrap(), Account::Bot);
+        assert_eq!(Account::from_str("bot:PepegaBot").unwrap(), Account::Identity("pepegabot".to_string()));
+        assert_eq!(Account::from_str("broadcaster:Pepega").unwrap(), Account::Broadcaster("pepega".to_string()));
+        assert!(Account::from_str("broadcaster:").is_err());
+        assert!(Account::from_str("forsen").is_err());
+    }
+
+    #[test]
+    fn status_line_works() {
+        assert_eq!(format_duration(Duration::from_secs(14399)), "3h 59m");
+        assert_eq!(format_duration(Duration::from_secs(600)), "10m");
+
+        let stored_token = StoredToken { access_token: "token".to_string(), refresh_token: Option::Some("refresh".to_string()) };
+        let validated_token = ValidatedToken {
+            client_id: ClientId::new("client-id".to_string()),
+            login: Option::Some("develbot".to_string()),
+            user_id: Option::Some("1".to_string()),
+            scopes: Option::Some(vec![Scope::ChatRead, Scope::ChatEdit]),
+            expires_in: Duration::from_secs(3660),
+        };
+
+        let line = format_status_line("bot", &stored_token, &Ok(validated_token));
+        assert!(line.starts_with("bot "));
+        assert!(line.contains("develbot"));
+        assert!(line.contains("expires in 1h 1m"));
+        assert!(line.ends_with("scopes: chat:read, chat:edit"));
+
+        let line = format_status_line("bot", &stored_token, &Err(ValidationError::NotAuthorized));
+        assert!(line.ends_with("expired, going to be refreshed on the next start"));
+    }
+
+    #[tokio::test]
+    async fn revoke_keeps_tokens_twitch_didnt_revoke() {
+        let config = Arc::new(RwLock::new(Config {
+            app_config: toml::from_str(CONFIG).unwrap(),
+            config_path: "config.toml".to_string(),
+        }));
+        let token_store = TokenStore::open(Arc::new(MemoryTokenRepository::default()), Option::None).await.unwrap();
+        let mut token_client = TokenClient::new(config, token_store).await.unwrap();
+
+        let token = UserToken::from_existing_unchecked(
+            AccessToken::new("bot-token".to_string()),
+            Option::None,
+            ClientId::new("client-id".to_string()),
+            Option::None,
+            "develbot".to_string(),
+            String::new(),
+            Option::None,
+            Option::None
+        );
+        token_client.token_store.set_user_token(&token).await.unwrap();
+        token_client.token_store.set_identity_token("pepegabot", Option::Some(&token)).await.unwrap();
+
+        let outage = MockOAuthServer::start(Arc::new(|_| (503, "Service Unavailable".to_string()))).await;
+        token_client.oauth_base_url = outage.base_url.clone();
+        let token_client = Arc::new(RwLock::new(token_client));
+
+        assert!(revoke(token_client.clone(), Option::Some(Account::Bot)).await.is_err());
+        assert!(token_client.read().await.token_store.tokens().user.is_some());
+
+        let invalid = MockOAuthServer::start(Arc::new(|_| (400, r#"{"status":400,"message":"Invalid token"}"#.to_string()))).await;
+        token_client.write().await.oauth_base_url = invalid.base_url.clone();
+
+        revoke(token_client.clone(), Option::None).await.unwrap();
+
+        let token_client = token_client.read().await;
+        assert!(token_client.token_store.tokens().user.is_none());
+        assert!(token_client.token_store.tokens().identities.is_empty());
+    }
+}
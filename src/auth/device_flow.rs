@@ -0,0 +1,234 @@
+//! Device authorization grant, lets headless deployments login without reaching the redirect endpoint
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use twitch_oauth2::{AccessToken, ClientSecret, RefreshToken, Scope, UserToken};
+use twitch_oauth2::oauth2::{HttpRequest, HttpResponse};
+use twitch_oauth2::oauth2::http::{header, HeaderMap, HeaderValue, Method};
+use url::Url;
+
+use crate::auth::AuthSettings;
+use crate::auth::http_client::{oauth_http_client, TWITCH_OAUTH_BASE_URL};
+
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+/// Added to polling interval every time Twitch asks to slow down
+const SLOW_DOWN_STEP_SEC: u64 = 5;
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub expires_in: u64,
+    pub interval: u64,
+    pub user_code: String,
+    pub verification_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeviceTokenResponse {
+    access_token: String,
+    refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum PollResult {
+    /// User hasn't entered the code yet
+    Pending,
+    SlowDown,
+    Token {
+        access_token: String,
+        refresh_token: String,
+    },
+}
+
+fn form_request(path: &str, params: &[(&str, &str)]) -> anyhow::Result<HttpRequest> {
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/x-www-form-urlencoded"));
+    headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+
+    Ok(HttpRequest {
+        url: Url::parse(format!("{}{}", TWITCH_OAUTH_BASE_URL, path).as_str())?,
+        method: Method::POST,
+        headers,
+        body: body.into_bytes(),
+    })
+}
+
+fn error_message(response: &HttpResponse) -> String {
+    serde_json::from_slice::<ErrorResponse>(&response.body)
+        .map(|error| error.message)
+        .unwrap_or_else(|_| String::from_utf8_lossy(&response.body).into_owned())
+}
+
+fn join_scopes(scopes: &[Scope]) -> String {
+    scopes.iter().map(|scope| scope.to_string()).collect::<Vec<String>>().join(" ")
+}
+
+pub async fn request_device_code(oauth_base_url: &str, client_id: &str, scopes: &[Scope]) -> anyhow::Result<DeviceAuthorization> {
+    let scopes = join_scopes(scopes);
+    let request = form_request("/device", &[("client_id", client_id), ("scopes", scopes.as_str())])?;
+    let response = oauth_http_client(oauth_base_url.to_string())(request).await?;
+
+    if !response.status_code.is_success() {
+        return Err(anyhow::anyhow!("Failed to start device login: {}", error_message(&response)));
+    }
+
+    Ok(serde_json::from_slice(&response.body)?)
+}
+
+async fn poll_once(oauth_base_url: &str, client_id: &str, scopes: &str, device_code: &str) -> anyhow::Result<PollResult> {
+    let request = form_request("/token", &[
+        ("client_id", client_id),
+        ("scopes", scopes),
+        ("device_code", device_code),
+        ("grant_type", DEVICE_CODE_GRANT_TYPE),
+    ])?;
+    let response = oauth_http_client(oauth_base_url.to_string())(request).await?;
+
+    if response.status_code.is_success() {
+        let token: DeviceTokenResponse = serde_json::from_slice(&response.body)?;
+
+        return Ok(PollResult::Token {
+            access_token: token.access_token,
+            refresh_token: token.refresh_token,
+        });
+    }
+
+    match error_message(&response).as_str() {
+        "authorization_pending" => Ok(PollResult::Pending),
+        "slow_down" => Ok(PollResult::SlowDown),
+        "expired_token" => Err(anyhow::anyhow!("Device code expired before the login was completed")),
+        "access_denied" => Err(anyhow::anyhow!("Login was declined")),
+        message => Err(anyhow::anyhow!("Device login failed: {}", message)),
+    }
+}
+
+/// Polls until the user enters the code, returns access and refresh tokens
+pub async fn poll_for_token(oauth_base_url: &str, client_id: &str, scopes: &[Scope], authorization: &DeviceAuthorization) -> anyhow::Result<(String, String)> {
+    let scopes = join_scopes(scopes);
+    let mut interval = Duration::from_secs(authorization.interval);
+
+    let polling = async {
+        loop {
+            tokio::time::sleep(interval).await;
+
+            match poll_once(oauth_base_url, client_id, scopes.as_str(), authorization.device_code.as_str()).await? {
+                PollResult::Pending => {},
+                PollResult::SlowDown => interval += Duration::from_secs(SLOW_DOWN_STEP_SEC),
+                PollResult::Token { access_token, refresh_token } => return Ok((access_token, refresh_token)),
+            }
+        }
+    };
+
+    tokio::time::timeout(Duration::from_secs(authorization.expires_in), polling).await
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("Device code expired before the login was completed")))
+}
+
+/// Runs the whole device flow, `account` is only a hint for whoever logs in
+pub async fn authorize_device(oauth_base_url: &str, settings: &AuthSettings, account: &str, scopes: Vec<Scope>) -> anyhow::Result<UserToken> {
+    let authorization = request_device_code(oauth_base_url, settings.client_id.as_str(), &scopes).await?;
+
+    log::info!(
+        "PLEASE LOGIN as {} at {} and enter code {} within {}s",
+        account,
+        authorization.verification_uri,
+        authorization.user_code,
+        authorization.expires_in
+    );
+
+    let (access_token, refresh_token) = poll_for_token(oauth_base_url, settings.client_id.as_str(), &scopes, &authorization).await?;
+
+    let token = UserToken::from_existing(
+        oauth_http_client(oauth_base_url.to_string()),
+        AccessToken::new(access_token),
+        RefreshToken::new(refresh_token),
+        ClientSecret::new(settings.client_secret.clone())
+    ).await?;
+
+    log::info!("Logged in as {}", token.login);
+
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use twitch_oauth2::Scope;
+
+    use crate::auth::AuthSettings;
+    use crate::auth::mock_oauth::MockOAuthServer;
+    use crate::config::LoginFlow;
+
+    use super::authorize_device;
+
+    #[tokio::test]
+    async fn device_flow_polls_until_login() {
+        let polls = Arc::new(AtomicUsize::new(0));
+
+        let server = MockOAuthServer::start(Arc::new({
+            let polls = polls.clone();
+
+            move |request| {
+                match request.path.as_str() {
+                    "/oauth2/device" => (200, r#"{"device_code":"device-code","expires_in":60,"interval":0,"user_code":"ABCDEFGH","verification_uri":"https://www.twitch.tv/activate"}"#.to_string()),
+                    "/oauth2/token" if polls.fetch_add(1, Ordering::SeqCst) < 2 => (400, r#"{"status":400,"message":"authorization_pending"}"#.to_string()),
+                    "/oauth2/token" => (200, r#"{"access_token":"user-token","refresh_token":"refresh-token","expires_in":14400,"scope":["chat:read"],"token_type":"bearer"}"#.to_string()),
+                    "/oauth2/validate" => (200, r#"{"client_id":"client-id","login":"develbot","user_id":"1","scopes":["chat:read"],"expires_in":14400}"#.to_string()),
+                    _ => (404, String::new()),
+                }
+            }
+        })).await;
+
+        let settings = AuthSettings {
+            client_id: "client-id".to_string(),
+            client_secret: "client-secret".to_string(),
+            auth_host: "localhost".to_string(),
+            auth_port: 8099,
+            login_flow: LoginFlow::Device,
+            timeout: Duration::from_secs(60),
+        };
+
+        let token = authorize_device(server.base_url.as_str(), &settings, "develbot", vec![Scope::ChatRead]).await.unwrap();
+
+        assert_eq!(token.login, "develbot");
+        assert_eq!(token.access_token.secret(), "user-token");
+        assert_eq!(polls.load(Ordering::SeqCst), 3);
+
+        let device_request = server.requests.lock().unwrap()[0].clone();
+        assert!(device_request.body.contains("scopes=chat%3Aread"));
+    }
+
+    #[tokio::test]
+    async fn device_flow_fails_on_denied_login() {
+        let server = MockOAuthServer::start(Arc::new(|request| {
+            match request.path.as_str() {
+                "/oauth2/device" => (200, r#"{"device_code":"device-code","expires_in":60,"interval":0,"user_code":"ABCDEFGH","verification_uri":"https://www.twitch.tv/activate"}"#.to_string()),
+                _ => (400, r#"{"status":400,"message":"access_denied"}"#.to_string()),
+            }
+        })).await;
+
+        let settings = AuthSettings {
+            client_id: "client-id".to_string(),
+            client_secret: "client-secret".to_string(),
+            auth_host: "localhost".to_string(),
+            auth_port: 8099,
+            login_flow: LoginFlow::Device,
+            timeout: Duration::from_secs(60),
+        };
+
+        let error = authorize_device(server.base_url.as_str(), &settings, "develbot", vec![Scope::ChatRead]).await.unwrap_err();
+        assert_eq!(error.to_string(), "Login was declined");
+    }
+}
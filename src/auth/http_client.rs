@@ -0,0 +1,48 @@
+use futures::future::BoxFuture;
+use twitch_oauth2::client::reqwest_http_client;
+use twitch_oauth2::oauth2::{HttpRequest, HttpResponse};
+use twitch_oauth2::oauth2::reqwest::Error;
+use url::Url;
+
+/// Base of every OAuth endpoint `twitch_oauth2` talks to
+pub const TWITCH_OAUTH_BASE_URL: &str = "https://id.twitch.tv/oauth2";
+
+pub type OAuthHttpError = Error<reqwest::Error>;
+
+/// Replaces `default_base_url` of the URL with `base_url`, so requests can be sent to a mock server
+pub fn rebase_url(url: &str, default_base_url: &str, base_url: &str) -> Option<String> {
+    let default_base_url = default_base_url.trim_end_matches('/');
+    let base_url = base_url.trim_end_matches('/');
+
+    if base_url == default_base_url {
+        return Option::None;
+    }
+
+    url.strip_prefix(default_base_url).map(|rest| format!("{}{}", base_url, rest))
+}
+
+/// HTTP client for `twitch_oauth2` functions that sends OAuth requests to `base_url` instead of Twitch
+pub fn oauth_http_client(base_url: String) -> impl Fn(HttpRequest) -> BoxFuture<'static, Result<HttpResponse, OAuthHttpError>> + Send + Sync {
+    move |mut request: HttpRequest| {
+        if let Some(url) = rebase_url(request.url.as_str(), TWITCH_OAUTH_BASE_URL, base_url.as_str()).and_then(|url| Url::parse(url.as_str()).ok()) {
+            request.url = url;
+        }
+
+        Box::pin(reqwest_http_client(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rebase_url, TWITCH_OAUTH_BASE_URL};
+
+    #[test]
+    fn rebase_url_works() {
+        let url = "https://id.twitch.tv/oauth2/token?grant_type=refresh_token";
+
+        assert_eq!(rebase_url(url, TWITCH_OAUTH_BASE_URL, "https://id.twitch.tv/oauth2"), Option::None);
+        assert_eq!(rebase_url(url, TWITCH_OAUTH_BASE_URL, "http://localhost:8080/auth/").as_deref(), Option::Some("http://localhost:8080/auth/token?grant_type=refresh_token"));
+        assert_eq!(rebase_url("https://api.twitch.tv/helix/users", TWITCH_OAUTH_BASE_URL, "http://localhost:8080/auth"), Option::None);
+        assert_eq!(rebase_url("https://api.twitch.tv/helix/users", "https://api.twitch.tv/helix/", "http://localhost:8080/mock/").as_deref(), Option::Some("http://localhost:8080/mock/users"));
+    }
+}
@@ -0,0 +1,95 @@
+//! Local stand-in for Twitch OAuth and Helix endpoints, used by tests
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use hyper::{Body, Request, Response, Server};
+use hyper::service::{make_service_fn, service_fn};
+
+#[derive(Clone, Debug)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub authorization: Option<String>,
+    pub body: String,
+}
+
+pub type MockHandler = Arc<dyn Fn(&MockRequest) -> (u16, String) + Send + Sync>;
+
+pub struct MockOAuthServer {
+    pub base_url: String,
+    pub requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockOAuthServer {
+    /// Starts the server on a random local port with OAuth's `/oauth2` base path
+    pub async fn start(handler: MockHandler) -> Self {
+        MockOAuthServer::start_at("/oauth2", handler).await
+    }
+
+    /// Starts the server on a random local port, `base_url` ends with `base_path`,
+    /// `handler` returns status code and JSON body for each request
+    pub async fn start_at(base_path: &str, handler: MockHandler) -> Self {
+        let requests: Arc<Mutex<Vec<MockRequest>>> = Arc::new(Mutex::new(vec![]));
+
+        let make_service = make_service_fn({
+            let requests = requests.clone();
+
+            move |_| {
+                let handler = handler.clone();
+                let requests = requests.clone();
+
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                        let handler = handler.clone();
+                        let requests = requests.clone();
+
+                        async move {
+                            let method = request.method().to_string();
+                            let path = request.uri().path().to_string();
+                            let authorization = request.headers()
+                                .get(hyper::header::AUTHORIZATION)
+                                .and_then(|value| value.to_str().ok())
+                                .map(|value| value.to_string());
+                            let body = hyper::body::to_bytes(request.into_body()).await.unwrap_or_default();
+
+                            let mock_request = MockRequest {
+                                method,
+                                path,
+                                authorization,
+                                body: String::from_utf8_lossy(&body).into_owned(),
+                            };
+
+                            let (status, body) = handler(&mock_request);
+                            requests.lock().unwrap().push(mock_request);
+
+                            let response = Response::builder()
+                                .status(status)
+                                .header("Content-Type", "application/json")
+                                .body(Body::from(body))
+                                .unwrap();
+
+                            Ok::<_, Infallible>(response)
+                        }
+                    }))
+                }
+            }
+        });
+
+        let address: SocketAddr = ([127, 0, 0, 1], 0).into();
+        let server = Server::bind(&address).serve(make_service);
+        let base_url = format!("http://{}{}", server.local_addr(), base_path);
+
+        tokio::spawn(server);
+
+        Self {
+            base_url,
+            requests,
+        }
+    }
+
+    pub fn requested_paths(&self) -> Vec<String> {
+        self.requests.lock().unwrap().iter().map(|request| request.path.clone()).collect()
+    }
+}
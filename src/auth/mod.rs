@@ -0,0 +1,940 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::{RwLock, watch};
+use tokio_util::sync::CancellationToken;
+use twitch_irc::login::{CredentialsPair, LoginCredentials};
+use twitch_oauth2::{AccessToken, AppAccessToken, ClientId, ClientSecret, RedirectUrl, RefreshToken, Scope, TwitchToken, UserToken};
+use twitch_oauth2::oauth2::RequestTokenError;
+use twitch_oauth2::tokens::UserTokenBuilder;
+use twitch_oauth2::tokens::errors::{RefreshTokenError, ValidationError};
+
+use crate::auth::callback::{CALLBACK_PATH, CallbackServer, DEFAULT_CALLBACK_TIMEOUT_SEC};
+use crate::auth::checker::{run_checker, TokenHealth};
+use crate::auth::device_flow::authorize_device;
+use crate::auth::http_client::{OAuthHttpError, oauth_http_client};
+use crate::auth::scopes::{Feature, format_scopes, missing_scopes};
+use crate::auth::token_store::{StoredToken, TokenStore};
+use crate::config::{Config, LoginFlow};
+
+pub mod callback;
+pub mod checker;
+pub mod cli;
+pub mod device_flow;
+pub mod http_client;
+pub mod scopes;
+pub mod token_store;
+#[cfg(test)]
+pub(crate) mod mock_oauth;
+
+/// Everything authorization code flow needs from the config, so it can run without holding config lock
+#[derive(Clone, Debug)]
+pub struct AuthSettings {
+    pub client_id: String,
+    pub client_secret: String,
+    pub auth_host: String,
+    pub auth_port: u64,
+    pub login_flow: LoginFlow,
+    pub timeout: Duration,
+}
+
+impl AuthSettings {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            client_id: config.app_config.twitch.client_id.clone(),
+            client_secret: config.app_config.twitch.client_secret.clone(),
+            auth_host: config.app_config.global.auth_host.clone(),
+            auth_port: config.app_config.global.auth_port,
+            login_flow: config.app_config.global.login_flow,
+            timeout: Duration::from_secs(config.app_config.global.auth_timeout_sec.unwrap_or(DEFAULT_CALLBACK_TIMEOUT_SEC)),
+        }
+    }
+}
+
+/// Twitch won't refresh the token anymore, e.g. it was revoked or its password changed, unlike transient failures
+#[derive(Debug)]
+pub struct RefreshRejected(String);
+
+impl Display for RefreshRejected {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Twitch rejected the refresh token: {}", self.0)
+    }
+}
+
+impl std::error::Error for RefreshRejected {}
+
+/// Wraps definitive refresh failures in `RefreshRejected`, so only these cost the token
+fn refresh_error(error: RefreshTokenError<OAuthHttpError>) -> anyhow::Error {
+    let rejection = match &error {
+        RefreshTokenError::NoRefreshToken => Option::Some(error.to_string()),
+        RefreshTokenError::RequestError(RequestTokenError::ServerResponse(response)) => {
+            let message = response.message.to_lowercase();
+            let rejected = response.status.as_u16() == 401
+                || (response.status.as_u16() == 400 && (message.contains("invalid refresh token") || message.contains("invalid_grant")));
+
+            if rejected { Option::Some(format!("{} {}", response.status, response.message)) } else { Option::None }
+        },
+        // OAuth standard error bodies don't parse into Twitch's error response
+        RefreshTokenError::RequestError(RequestTokenError::Parse(_, body)) => {
+            let body = String::from_utf8_lossy(body);
+
+            if body.contains("invalid_grant") { Option::Some(body.to_string()) } else { Option::None }
+        },
+        _ => Option::None,
+    };
+
+    match rejection {
+        Some(reason) => anyhow::Error::new(RefreshRejected(reason)),
+        None => anyhow::anyhow!("Couldn't refresh the token: {}", error),
+    }
+}
+
+pub struct TokenClient {
+    pub app_token: Option<AppAccessToken>,
+    /// Channels owners' tokens by channel login, needed by channel-owner-only APIs
+    pub broadcaster_tokens: HashMap<String, UserToken>,
+    cancellation_token: CancellationToken,
+    pub config: Arc<RwLock<Config>>,
+    pub check_interval: u64,
+    health: Arc<watch::Sender<TokenHealth>>,
+    health_receiver: watch::Receiver<TokenHealth>,
+    /// Additional bot identities' tokens by login
+    pub identity_tokens: HashMap<String, UserToken>,
+    /// Base URL of OAuth endpoints, Twitch's unless pointed to a mock server
+    pub oauth_base_url: String,
+    pub token_store: TokenStore,
+    pub user_token: Option<UserToken>,
+    pub validity_period: u64,
+}
+
+impl TokenClient {
+    pub async fn new(config: Arc<RwLock<Config>>, token_store: TokenStore) -> anyhow::Result<Self> {
+        let config_clone = config.clone();
+        let lock = config_clone.read().await;
+        let check_interval = lock.app_config.twitch.check_every_sec.unwrap_or(15);
+        let (health, health_receiver) = watch::channel(TokenHealth::Starting);
+
+        Ok(TokenClient {
+            app_token: Option::None,
+            broadcaster_tokens: HashMap::new(),
+            cancellation_token: CancellationToken::new(),
+            check_interval,
+            config,
+            health: Arc::new(health),
+            health_receiver,
+            identity_tokens: HashMap::new(),
+            oauth_base_url: lock.app_config.twitch.oauth_url(),
+            token_store,
+            user_token: Option::None,
+            validity_period: check_interval * 2
+        })
+    }
+
+    /// Receiver of token checks' health, the latest state is available via `borrow()`
+    pub fn get_health(&self) -> watch::Receiver<TokenHealth> {
+        self.health_receiver.clone()
+    }
+
+    pub fn set_user_token(&mut self, token: UserToken) {
+        self.user_token = Option::Some(token);
+    }
+
+    /// Validates the token and refreshes it if it's about to expire, returns whether it was refreshed,
+    /// fails with `RefreshRejected` only if the token can't be refreshed anymore
+    async fn refresh_if_needed(&self, token: &mut UserToken, validity_period: u64) -> anyhow::Result<bool> {
+        let needs_refresh = match token.validate_token(oauth_http_client(self.oauth_base_url.clone())).await {
+            Err(ValidationError::NotAuthorized) => true,
+            // Network errors and Twitch outages say nothing about the token
+            Err(error) => return Err(anyhow::anyhow!("Couldn't validate the token: {}", error)),
+            Ok(validated_token) => !token.never_expires() && validated_token.expires_in.as_secs() <= validity_period,
+        };
+
+        if needs_refresh {
+            token.refresh_token(oauth_http_client(self.oauth_base_url.clone())).await.map_err(refresh_error)?;
+        }
+
+        Ok(needs_refresh)
+    }
+
+    /// Validates the user token and refreshes it, if it's about to expire
+    pub async fn check_user_token(token_client: &mut TokenClient, validity_period: u64) -> anyhow::Result<()> {
+        let mut token = token_client.user_token.clone().ok_or_else(|| anyhow::anyhow!("Can't check user token, there's none"))?;
+
+        if token_client.refresh_if_needed(&mut token, validity_period).await? {
+            token_client.token_store.set_user_token(&token).await?;
+            token_client.set_user_token(token);
+
+            log::info!("Refreshed user token");
+        }
+
+        log::debug!("Checked user token");
+
+        Ok(())
+    }
+
+    /// Refreshes identity's token if it's about to expire, it's dropped only if Twitch won't refresh it anymore
+    pub async fn check_identity_token(token_client: &mut TokenClient, login: &str, validity_period: u64) -> anyhow::Result<()> {
+        let login = login.to_lowercase();
+
+        let mut token = match token_client.identity_tokens.get(&login) {
+            Some(token) => token.clone(),
+            None => return Ok(()),
+        };
+
+        match token_client.refresh_if_needed(&mut token, validity_period).await {
+            Ok(true) => {
+                token_client.token_store.set_identity_token(login.as_str(), Option::Some(&token)).await?;
+                token_client.identity_tokens.insert(login.clone(), token);
+
+                log::info!("Refreshed token of identity '{}'", login);
+            },
+            Ok(false) => {},
+            Err(error) if error.is::<RefreshRejected>() => {
+                log::error!("Identity '{}' has to login again: {}", login, error);
+
+                token_client.token_store.set_identity_token(login.as_str(), Option::None).await?;
+                token_client.identity_tokens.remove(&login);
+            },
+            Err(error) => return Err(anyhow::anyhow!("Couldn't check token of identity '{}': {}", login, error)),
+        }
+
+        Ok(())
+    }
+
+    /// Checks every additional identity's token, the rest are checked even if one fails
+    pub async fn check_identity_tokens(token_client: &mut TokenClient, validity_period: u64) -> anyhow::Result<()> {
+        let logins: Vec<String> = token_client.identity_tokens.keys().cloned().collect();
+        let mut errors: Vec<String> = vec![];
+
+        for login in logins {
+            if let Err(error) = TokenClient::check_identity_token(token_client, login.as_str(), validity_period).await {
+                errors.push(error.to_string());
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(anyhow::anyhow!(errors.join("; ")));
+        }
+
+        log::debug!("Checked identity tokens");
+
+        Ok(())
+    }
+
+    /// Refreshes broadcaster tokens about to expire, the ones that can't be refreshed anymore are dropped
+    pub async fn check_broadcaster_tokens(token_client: &mut TokenClient, validity_period: u64) -> anyhow::Result<()> {
+        let channels: Vec<String> = token_client.broadcaster_tokens.keys().cloned().collect();
+        let mut errors: Vec<String> = vec![];
+
+        for channel in channels {
+            let mut token = token_client.broadcaster_tokens[&channel].clone();
+
+            match token_client.refresh_if_needed(&mut token, validity_period).await {
+                Ok(true) => {
+                    token_client.token_store.set_broadcaster_token(channel.as_str(), Option::Some(&token)).await?;
+                    token_client.broadcaster_tokens.insert(channel.clone(), token);
+
+                    log::info!("Refreshed broadcaster token of channel '{}'", channel);
+                },
+                Ok(false) => {},
+                Err(error) if error.is::<RefreshRejected>() => {
+                    log::error!("Broadcaster of channel '{}' has to login again: {}", channel, error);
+
+                    token_client.token_store.set_broadcaster_token(channel.as_str(), Option::None).await?;
+                    token_client.broadcaster_tokens.remove(&channel);
+                },
+                // Kept for the next check, the checker backs off meanwhile
+                Err(error) => errors.push(format!("Couldn't check broadcaster token of channel '{}': {}", channel, error)),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(anyhow::anyhow!(errors.join("; ")));
+        }
+
+        log::debug!("Checked broadcaster tokens");
+
+        Ok(())
+    }
+
+    pub async fn check_app_token(token_client: &mut TokenClient, config: &Config, token: Option<AppAccessToken>, validity_period: u64) -> anyhow::Result<()> {
+        let token = token.filter(|token| !token.access_token.secret().is_empty());
+
+        if let Some(token) = token {
+            match token.validate_token(oauth_http_client(token_client.oauth_base_url.clone())).await {
+                Err(_) => {
+                    TokenClient::get_app_token(token_client, config).await?;
+                },
+                Ok(validated_token) => {
+                    if validated_token.expires_in.as_secs() <= validity_period {
+                        TokenClient::get_app_token(token_client, config).await?;
+                    }
+                },
+            }
+        } else {
+            TokenClient::get_app_token(token_client, config).await?;
+        }
+
+        log::debug!("Checked twitch token");
+
+        Ok(())
+    }
+
+    pub async fn get_app_token(token_client: &mut TokenClient, config: &Config) -> anyhow::Result<()> {
+        let client_id = ClientId::new(config.app_config.twitch.client_id.clone());
+        let client_secret = ClientSecret::new(config.app_config.twitch.client_secret.clone());
+        // App tokens come from client credentials flow, user scopes can't be granted to them
+        let token = AppAccessToken::get_app_access_token(oauth_http_client(token_client.oauth_base_url.clone()), client_id, client_secret, vec![]).await?;
+
+        token_client.token_store.set_app_token(&token).await?;
+        token_client.app_token = Option::Some(token);
+
+        Ok(())
+    }
+
+    /// Loads stored token of bot's account, asks it to login if there's no usable one, the lock isn't held during the login
+    pub async fn get_user_token(this: &Arc<RwLock<TokenClient>>, config: &Config) -> anyhow::Result<()> {
+        let bot_name = config.app_config.twitch.bot_name.clone();
+        let stored_token = this.read().await.token_store.tokens().user.clone();
+
+        let (token, changed) = TokenClient::acquire_account_token(this, config, stored_token, bot_name.as_str()).await?;
+        let mut this_lock = this.write().await;
+
+        if changed {
+            this_lock.token_store.set_user_token(&token).await?;
+        }
+
+        this_lock.set_user_token(token);
+
+        Ok(())
+    }
+
+    /// Loads stored token of additional identity, asks the account to login if there's no usable one, the lock isn't held during the login
+    pub async fn get_identity_token(this: &Arc<RwLock<TokenClient>>, config: &Config, login: &str) -> anyhow::Result<()> {
+        let stored_token = this.read().await.token_store.tokens().identities.get(login).cloned();
+
+        let (token, changed) = TokenClient::acquire_account_token(this, config, stored_token, login).await?;
+        let mut this_lock = this.write().await;
+
+        if changed {
+            this_lock.token_store.set_identity_token(login, Option::Some(&token)).await?;
+        }
+
+        this_lock.identity_tokens.insert(login.to_lowercase(), token);
+
+        Ok(())
+    }
+
+    /// Stored token of given bot's account or a new one it logged in with, second value tells whether it has to be stored
+    async fn acquire_account_token(this: &Arc<RwLock<TokenClient>>, config: &Config, stored_token: Option<StoredToken>, login: &str) -> anyhow::Result<(UserToken, bool)> {
+        let oauth_base_url = {
+            let this_lock = this.read().await;
+
+            if let Some(loaded) = TokenClient::load_account_token(&this_lock, config, stored_token, login).await {
+                return Ok(loaded);
+            }
+
+            this_lock.oauth_base_url.clone()
+        };
+
+        let token = TokenClient::authorize_account(oauth_base_url.as_str(), config, login).await?;
+
+        Ok((token, true))
+    }
+
+    /// Loads stored token of bot's account, `None` if it's unusable or lacks required scopes
+    async fn load_account_token(token_client: &TokenClient, config: &Config, stored_token: Option<StoredToken>, login: &str) -> Option<(UserToken, bool)> {
+        let required_scopes = config.app_config.twitch.required_scopes();
+        let stored_token = stored_token.unwrap_or_default();
+
+        let (token, refreshed) = TokenClient::load_stored_token(token_client, config, Option::Some(stored_token.access_token), stored_token.refresh_token, login).await?;
+        let missing = missing_scopes(&required_scopes, token.scopes());
+
+        if !missing.is_empty() {
+            log::warn!("Stored token of '{}' lacks scopes needed by enabled features: {}, please login again", login, format_scopes(&missing));
+            return Option::None;
+        }
+
+        Option::Some((token, refreshed))
+    }
+
+    /// Asks bot's account to login and stores the token
+    pub async fn authorize_bot(token_client: &mut TokenClient, config: &Config) -> anyhow::Result<()> {
+        let bot_name = config.app_config.twitch.bot_name.clone();
+        let token = TokenClient::authorize_account(token_client.oauth_base_url.as_str(), config, bot_name.as_str()).await?;
+
+        token_client.token_store.set_user_token(&token).await?;
+        token_client.set_user_token(token);
+
+        Ok(())
+    }
+
+    /// Asks additional identity's account to login and stores the token
+    pub async fn authorize_identity(token_client: &mut TokenClient, config: &Config, login: &str) -> anyhow::Result<()> {
+        let token = TokenClient::authorize_account(token_client.oauth_base_url.as_str(), config, login).await?;
+
+        token_client.token_store.set_identity_token(login, Option::Some(&token)).await?;
+        token_client.identity_tokens.insert(login.to_lowercase(), token);
+
+        Ok(())
+    }
+
+    /// Asks given bot's account to login, making sure it's the right account and it granted required scopes
+    async fn authorize_account(oauth_base_url: &str, config: &Config, login: &str) -> anyhow::Result<UserToken> {
+        let required_scopes = config.app_config.twitch.required_scopes();
+
+        let settings = AuthSettings::from_config(config);
+        let token = TokenClient::authorize_user(oauth_base_url, &settings, login, required_scopes.clone()).await?;
+
+        if !token.login.eq_ignore_ascii_case(login) {
+            return Err(anyhow::anyhow!("Logged in as '{}', but bot's account is '{}'", token.login, login));
+        }
+
+        let missing = missing_scopes(&required_scopes, token.scopes());
+
+        if !missing.is_empty() {
+            return Err(anyhow::anyhow!("User token wasn't granted scopes needed by enabled features: {}", format_scopes(&missing)));
+        }
+
+        Ok(token)
+    }
+
+    /// Loads stored broadcaster token, without asking anyone to login, returns whether there's a usable one
+    pub async fn get_broadcaster_token(token_client: &mut TokenClient, config: &Config, channel: &str) -> anyhow::Result<bool> {
+        let stored_token = token_client.token_store.tokens().broadcasters.get(channel.to_lowercase().as_str()).cloned().unwrap_or_default();
+        let access_token = Option::Some(stored_token.access_token);
+        let refresh_token = stored_token.refresh_token;
+
+        let (token, refreshed) = match TokenClient::load_stored_token(token_client, config, access_token, refresh_token, channel).await {
+            Some(token) => token,
+            None => return Ok(false),
+        };
+
+        let missing = missing_scopes(&config.app_config.twitch.broadcaster_scopes(), token.scopes());
+
+        if !missing.is_empty() {
+            log::warn!("Broadcaster token of channel '{}' lacks scopes needed by enabled features: {}", channel, format_scopes(&missing));
+            return Ok(false);
+        }
+
+        if refreshed {
+            token_client.token_store.set_broadcaster_token(channel, Option::Some(&token)).await?;
+        }
+
+        token_client.broadcaster_tokens.insert(channel.to_string(), token);
+
+        Ok(true)
+    }
+
+    /// Asks channel's broadcaster to login, locks are only held before and after the login
+    pub async fn authorize_broadcaster(this: Arc<RwLock<TokenClient>>, channel: &str) -> anyhow::Result<()> {
+        let (oauth_base_url, settings, scopes) = {
+            let this_lock = this.read().await;
+            let config = this_lock.config.read().await;
+
+            (this_lock.oauth_base_url.clone(), AuthSettings::from_config(&config), config.app_config.twitch.broadcaster_scopes())
+        };
+
+        let token = TokenClient::authorize_user(oauth_base_url.as_str(), &settings, channel, scopes.clone()).await?;
+
+        if !token.login.eq_ignore_ascii_case(channel) {
+            return Err(anyhow::anyhow!("Logged in as '{}', but broadcaster of channel '{}' has to login", token.login, channel));
+        }
+
+        let missing = missing_scopes(&scopes, token.scopes());
+
+        if !missing.is_empty() {
+            return Err(anyhow::anyhow!("Broadcaster token wasn't granted scopes needed by enabled features: {}", format_scopes(&missing)));
+        }
+
+        let mut this_lock = this.write().await;
+
+        this_lock.token_store.set_broadcaster_token(channel, Option::Some(&token)).await?;
+        this_lock.broadcaster_tokens.insert(channel.to_lowercase(), token);
+
+        Ok(())
+    }
+
+    /// User token of the identity, the default one for `None`
+    pub fn identity_token(&self, identity: Option<&str>) -> Option<&UserToken> {
+        match identity {
+            Some(login) => self.identity_tokens.get(login.to_lowercase().as_str()),
+            None => self.user_token.as_ref(),
+        }
+    }
+
+    /// Token to call APIs of given feature with, broadcaster's one if the feature needs it, channel's identity's one otherwise
+    pub fn token_for(&self, channel: &str, identity: Option<&str>, feature: Feature) -> anyhow::Result<UserToken> {
+        if feature.requires_broadcaster() {
+            return self.broadcaster_tokens.get(channel.to_lowercase().as_str())
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("No broadcaster token for channel '{}', broadcaster has to login first", channel));
+        }
+
+        self.identity_token(identity).cloned().ok_or_else(|| anyhow::anyhow!("No user token available"))
+    }
+
+    /// Validates stored token, refreshing it if needed, second value tells whether it was refreshed
+    async fn load_stored_token(
+        token_client: &TokenClient,
+        config: &Config,
+        access_token: Option<String>,
+        refresh_token: Option<String>,
+        login: &str,
+    ) -> Option<(UserToken, bool)> {
+        let http_client = oauth_http_client(token_client.oauth_base_url.clone());
+        let client_id = ClientId::new(config.app_config.twitch.client_id.clone());
+        let client_secret = ClientSecret::new(config.app_config.twitch.client_secret.clone());
+
+        let access_token = AccessToken::new(access_token.filter(|token| !token.is_empty())?);
+        let refresh_token = RefreshToken::new(refresh_token.filter(|token| !token.is_empty())?);
+
+        if let Ok(token) = UserToken::from_existing(&http_client, access_token, refresh_token.clone(), client_secret.clone()).await {
+            return Option::Some((token, false));
+        }
+
+        // Access token is no longer valid, but refresh token might still be
+        let mut token = UserToken::from_existing_unchecked(
+            AccessToken::new(String::new()),
+            refresh_token,
+            client_id,
+            client_secret.clone(),
+            login.to_string(),
+            String::new(),
+            Option::None,
+            Option::Some(Duration::from_secs(0))
+        );
+
+        token.refresh_token(&http_client).await.ok()?;
+
+        UserToken::from_existing(&http_client, token.access_token, token.refresh_token, client_secret).await
+            .ok()
+            .map(|token| (token, true))
+    }
+
+    /// Asks a user to login with configured flow, `account` is only a hint for whoever logs in, any account can complete it
+    pub async fn authorize_user(oauth_base_url: &str, settings: &AuthSettings, account: &str, scopes: Vec<Scope>) -> anyhow::Result<UserToken> {
+        match settings.login_flow {
+            LoginFlow::Redirect => TokenClient::authorize_with_redirect(oauth_base_url, settings, account, scopes).await,
+            LoginFlow::Device => authorize_device(oauth_base_url, settings, account, scopes).await,
+        }
+    }
+
+    /// Runs authorization code flow, waiting for the redirect on `auth_host:auth_port`
+    async fn authorize_with_redirect(oauth_base_url: &str, settings: &AuthSettings, account: &str, scopes: Vec<Scope>) -> anyhow::Result<UserToken> {
+        let http_client = oauth_http_client(oauth_base_url.to_string());
+        let client_id = ClientId::new(settings.client_id.clone());
+        let client_secret = ClientSecret::new(settings.client_secret.clone());
+
+        let host_port = format!("{}:{}", settings.auth_host, settings.auth_port);
+        let redirect_url = RedirectUrl::new(format!("http://{}{}", host_port, CALLBACK_PATH))?;
+        let timeout = settings.timeout;
+
+        let mut builder = UserTokenBuilder::new(client_id.clone(), client_secret.clone(), redirect_url.clone())?
+            .set_scopes(scopes)
+            .force_verify(true);
+        let (authorize_url, csrf) = builder.generate_url();
+
+        let address = tokio::net::lookup_host(host_port.as_str()).await?
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Couldn't resolve oauth callback address '{}'", host_port))?;
+        let server = CallbackServer::bind(&address, csrf.secret().clone())?;
+
+        log::debug!("Listening for oauth callback on {}", server.local_addr());
+
+        log::info!("PLEASE LOGIN as {} at given URL within {}s:\n{}", account, timeout.as_secs(), authorize_url.to_string());
+
+        let code = server.wait(timeout).await?;
+
+        log::debug!("Received code & state, exchanging code for the token");
+
+        builder.set_csrf(csrf.clone());
+        let token = builder.get_user_token(&http_client, csrf.secret(), code.as_str()).await?;
+
+        log::info!("Logged in as {}", token.login);
+
+        Ok(token)
+    }
+
+    /// Validates every token, acquiring or refreshing them as needed, the rest are checked even if one fails
+    pub async fn check_tokens(token_client: &mut TokenClient) -> anyhow::Result<()> {
+        let config_clone = token_client.config.clone();
+        let config_lock = config_clone.read().await;
+
+        let token = token_client.app_token.clone();
+        let validity_period = token_client.validity_period;
+
+        let results = vec![
+            TokenClient::check_app_token(token_client, &config_lock, token, validity_period).await,
+            TokenClient::check_user_token(token_client, validity_period).await,
+            TokenClient::check_identity_tokens(token_client, validity_period).await,
+            TokenClient::check_broadcaster_tokens(token_client, validity_period).await,
+        ];
+
+        let errors: Vec<String> = results.into_iter()
+            .filter_map(|result| result.err())
+            .map(|error| error.to_string())
+            .collect();
+
+        if !errors.is_empty() {
+            return Err(anyhow::anyhow!(errors.join("; ")));
+        }
+
+        Ok(())
+    }
+
+    pub async fn start(this: Arc<RwLock<TokenClient>>) -> anyhow::Result<()> {
+        let config_clone = this.read().await.config.clone();
+
+        // Logins can take up to `auth_timeout_sec`, the lock is only taken to store their tokens
+        {
+            let config_lock = config_clone.read().await;
+
+            TokenClient::get_user_token(&this, &config_lock).await?;
+
+            for login in config_lock.app_config.identity_names() {
+                TokenClient::get_identity_token(&this, &config_lock, login.as_str()).await?;
+            }
+        }
+
+        let (period, health, cancellation_token) = {
+            let mut this_lock = this.write().await;
+
+            let app_token = {
+                let config_lock = config_clone.read().await;
+
+                let client_id = ClientId::new(config_lock.app_config.twitch.client_id.clone());
+                let client_secret = ClientSecret::new(config_lock.app_config.twitch.client_secret.clone());
+
+                let stored_token = this_lock.token_store.tokens().app.clone().unwrap_or_default();
+                let app_access_token = AccessToken::new(stored_token.access_token);
+                let app_refresh_token = RefreshToken::new(stored_token.refresh_token.unwrap_or_default());
+
+                AppAccessToken::from_existing_unchecked(app_access_token, app_refresh_token, client_id, client_secret, Option::None, Option::None, Option::None)
+            };
+
+            // Only stored tokens for now, logins would hold up the bot until every broadcaster shows up
+            let missing_broadcasters = TokenClient::load_broadcaster_tokens(&mut this_lock).await?;
+
+            if !missing_broadcasters.is_empty() {
+                let this = this.clone();
+
+                tokio::spawn(async move {
+                    for channel in missing_broadcasters {
+                        if let Err(error) = TokenClient::authorize_broadcaster(this.clone(), channel.as_str()).await {
+                            log::error!("Failed to get broadcaster token of channel '{}': {}", channel, error);
+                        }
+                    }
+                });
+            }
+
+            this_lock.app_token = Option::Some(app_token);
+
+            // There's nothing to run the bot with, unless the first check succeeds
+            TokenClient::check_tokens(&mut this_lock).await?;
+
+            log::debug!("First token check completed!");
+
+            (Duration::from_secs(this_lock.check_interval), this_lock.health.clone(), this_lock.cancellation_token.clone())
+        };
+
+        tokio::spawn(async move {
+            // First check is already done
+            tokio::time::sleep(period).await;
+
+            run_checker(period, &health, cancellation_token, move || {
+                let this = this.clone();
+
+                async move {
+                    let mut this_lock = this.write().await;
+
+                    TokenClient::check_tokens(&mut this_lock).await
+                }
+            }).await;
+        });
+
+        Ok(())
+    }
+
+    /// Loads stored broadcaster tokens if enabled features need them, returns channels that have no usable token
+    async fn load_broadcaster_tokens(token_client: &mut TokenClient) -> anyhow::Result<Vec<String>> {
+        let config_clone = token_client.config.clone();
+        let config_lock = config_clone.read().await;
+
+        if config_lock.app_config.twitch.broadcaster_scopes().is_empty() {
+            return Ok(vec![]);
+        }
+
+        let channels: Vec<String> = config_lock.app_config.channels.iter()
+            .map(|channel_info| channel_info.channel.to_lowercase())
+            .collect();
+        let mut missing: Vec<String> = vec![];
+
+        for channel in channels {
+            if !TokenClient::get_broadcaster_token(token_client, &config_lock, channel.as_str()).await? {
+                missing.push(channel);
+            }
+        }
+
+        Ok(missing)
+    }
+
+    pub fn stop(&mut self) {
+        self.cancellation_token.cancel();
+    }
+}
+
+impl Drop for TokenClient {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Gives IRC client the user token of an identity, kept fresh by `TokenClient`'s checker
+pub struct UserTokenCredentials {
+    /// `None` for the default identity
+    identity: Option<String>,
+    token_client: Arc<RwLock<TokenClient>>,
+}
+
+impl UserTokenCredentials {
+    pub fn new(token_client: Arc<RwLock<TokenClient>>, identity: Option<String>) -> Self {
+        Self {
+            identity,
+            token_client,
+        }
+    }
+}
+
+impl Debug for UserTokenCredentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UserTokenCredentials").field("identity", &self.identity).finish()
+    }
+}
+
+#[async_trait]
+impl LoginCredentials for UserTokenCredentials {
+    type Error = anyhow::Error;
+
+    // Background checker keeps the token fresh, so (re)connects only read it
+    async fn get_credentials(&self) -> Result<CredentialsPair, Self::Error> {
+        let token_client = self.token_client.read().await;
+        let token = token_client.identity_token(self.identity.as_deref()).ok_or_else(|| anyhow::anyhow!("No user token available"))?;
+
+        Ok(CredentialsPair {
+            login: token.login.clone(),
+            token: Option::Some(token.access_token.secret().clone()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::RwLock;
+    use twitch_oauth2::{AccessToken, AppAccessToken, ClientId, ClientSecret, RefreshToken, TwitchToken, UserToken};
+
+    use crate::auth::mock_oauth::{MockHandler, MockOAuthServer};
+    use crate::auth::scopes::Feature;
+    use crate::auth::token_store::{StoredToken, TokenStore};
+    use crate::config::Config;
+    use crate::database::repository::memory::MemoryTokenRepository;
+
+    use super::TokenClient;
+
+    const CONFIG: &str = r#"
+        channels = []
+
+        [global]
+        auth_host = 'localhost'
+        auth_port = 8099
+
+        [database]
+        backend = 'memory'
+
+        [twitch]
+        bot_name = 'develbot'
+        client_id = 'client-id'
+        client_secret = 'client-secret'
+    "#;
+
+    async fn test_token_client() -> TokenClient {
+        let config = Arc::new(RwLock::new(Config {
+            app_config: toml::from_str(CONFIG).unwrap(),
+            config_path: "config.toml".to_string(),
+        }));
+        let token_store = TokenStore::open(Arc::new(MemoryTokenRepository::default()), Option::None).await.unwrap();
+
+        TokenClient::new(config, token_store).await.unwrap()
+    }
+
+    fn user_token(access_token: &str, login: &str) -> UserToken {
+        UserToken::from_existing_unchecked(
+            AccessToken::new(access_token.to_string()),
+            Option::None,
+            ClientId::new("client-id".to_string()),
+            Option::None,
+            login.to_string(),
+            String::new(),
+            Option::None,
+            Option::None
+        )
+    }
+
+    #[tokio::test]
+    async fn token_for_picks_broadcaster_token() {
+        let mut token_client = test_token_client().await;
+
+        token_client.user_token = Option::Some(user_token("bot-token", "develbot"));
+        assert!(token_client.token_for("Pepega", Option::None, Feature::Polls).is_err());
+
+        token_client.broadcaster_tokens.insert("pepega".to_string(), user_token("broadcaster-token", "pepega"));
+
+        assert_eq!(token_client.token_for("Pepega", Option::None, Feature::Polls).unwrap().token().secret(), "broadcaster-token");
+        assert_eq!(token_client.token_for("Pepega", Option::None, Feature::Chat).unwrap().token().secret(), "bot-token");
+    }
+
+    #[tokio::test]
+    async fn token_for_picks_identity_token() {
+        let mut token_client = test_token_client().await;
+
+        token_client.user_token = Option::Some(user_token("bot-token", "develbot"));
+        assert!(token_client.token_for("Pepega", Option::Some("PepegaBot"), Feature::Chat).is_err());
+
+        token_client.identity_tokens.insert("pepegabot".to_string(), user_token("identity-token", "pepegabot"));
+
+        assert_eq!(token_client.token_for("Pepega", Option::Some("PepegaBot"), Feature::Chat).unwrap().token().secret(), "identity-token");
+        assert_eq!(token_client.token_for("Pepega", Option::None, Feature::Chat).unwrap().token().secret(), "bot-token");
+    }
+
+    #[tokio::test]
+    async fn invalid_app_token_is_replaced() {
+        let server = MockOAuthServer::start(Arc::new(|request| {
+            match request.path.as_str() {
+                "/oauth2/validate" if request.authorization.as_deref() == Option::Some("OAuth new-app-token") => {
+                    (200, r#"{"client_id":"client-id","login":null,"user_id":null,"scopes":[],"expires_in":3600}"#.to_string())
+                },
+                "/oauth2/validate" => (401, r#"{"status":401,"message":"invalid access token"}"#.to_string()),
+                "/oauth2/token" => (200, r#"{"access_token":"new-app-token","expires_in":3600,"token_type":"bearer"}"#.to_string()),
+                _ => (404, String::new()),
+            }
+        })).await;
+
+        let mut token_client = test_token_client().await;
+        token_client.oauth_base_url = server.base_url.clone();
+
+        let old_token = AppAccessToken::from_existing_unchecked(
+            AccessToken::new("old-app-token".to_string()),
+            Option::None,
+            ClientId::new("client-id".to_string()),
+            ClientSecret::new("client-secret".to_string()),
+            Option::None,
+            Option::None,
+            Option::None
+        );
+
+        let config = token_client.config.clone();
+        TokenClient::check_app_token(&mut token_client, &*config.read().await, Option::Some(old_token), 60).await.unwrap();
+
+        assert_eq!(token_client.app_token.as_ref().unwrap().token().secret(), "new-app-token");
+        assert_eq!(token_client.token_store.tokens().app.as_ref().unwrap().access_token, "new-app-token");
+        assert_eq!(server.requested_paths(), vec!["/oauth2/validate", "/oauth2/token", "/oauth2/validate"]);
+
+        let token_request = server.requests.lock().unwrap()[1].clone();
+        assert_eq!(token_request.method, "POST");
+        assert!(token_request.body.contains("grant_type=client_credentials"));
+    }
+
+    fn refreshable_token(access_token: &str, login: &str) -> UserToken {
+        UserToken::from_existing_unchecked(
+            AccessToken::new(access_token.to_string()),
+            Option::Some(RefreshToken::new("refresh-token".to_string())),
+            ClientId::new("client-id".to_string()),
+            Option::Some(ClientSecret::new("client-secret".to_string())),
+            login.to_string(),
+            String::new(),
+            Option::None,
+            Option::None
+        )
+    }
+
+    /// Rejects every access token and answers refreshes with the given status and body
+    fn failing_refresh(status: u16, body: &'static str) -> MockHandler {
+        Arc::new(move |request| {
+            match request.path.as_str() {
+                "/oauth2/validate" => (401, r#"{"status":401,"message":"invalid access token"}"#.to_string()),
+                "/oauth2/token" => (status, body.to_string()),
+                _ => (404, String::new()),
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn identity_token_is_dropped_only_when_rejected() {
+        let mut token_client = test_token_client().await;
+        let token = refreshable_token("identity-token", "pepegabot");
+
+        token_client.token_store.set_identity_token("pepegabot", Option::Some(&token)).await.unwrap();
+        token_client.identity_tokens.insert("pepegabot".to_string(), token);
+
+        let outage = MockOAuthServer::start(failing_refresh(500, r#"{"status":500,"message":"Internal Server Error"}"#)).await;
+        token_client.oauth_base_url = outage.base_url.clone();
+
+        assert!(TokenClient::check_identity_tokens(&mut token_client, 60).await.is_err());
+        assert!(token_client.identity_tokens.contains_key("pepegabot"));
+        assert!(token_client.token_store.tokens().identities.contains_key("pepegabot"));
+
+        let rejection = MockOAuthServer::start(failing_refresh(400, r#"{"status":400,"message":"Invalid refresh token"}"#)).await;
+        token_client.oauth_base_url = rejection.base_url.clone();
+
+        TokenClient::check_identity_tokens(&mut token_client, 60).await.unwrap();
+        assert!(!token_client.identity_tokens.contains_key("pepegabot"));
+        assert!(!token_client.token_store.tokens().identities.contains_key("pepegabot"));
+    }
+
+    #[tokio::test]
+    async fn broadcaster_token_is_dropped_only_when_rejected() {
+        let mut token_client = test_token_client().await;
+        let token = refreshable_token("broadcaster-token", "pepega");
+
+        token_client.token_store.set_broadcaster_token("pepega", Option::Some(&token)).await.unwrap();
+        token_client.broadcaster_tokens.insert("pepega".to_string(), token);
+
+        let outage = MockOAuthServer::start(failing_refresh(503, r#"{"status":503,"message":"Service Unavailable"}"#)).await;
+        token_client.oauth_base_url = outage.base_url.clone();
+
+        assert!(TokenClient::check_broadcaster_tokens(&mut token_client, 60).await.is_err());
+        assert!(token_client.broadcaster_tokens.contains_key("pepega"));
+        assert!(token_client.token_store.tokens().broadcasters.contains_key("pepega"));
+
+        let rejection = MockOAuthServer::start(failing_refresh(400, r#"{"error":"invalid_grant"}"#)).await;
+        token_client.oauth_base_url = rejection.base_url.clone();
+
+        TokenClient::check_broadcaster_tokens(&mut token_client, 60).await.unwrap();
+        assert!(!token_client.broadcaster_tokens.contains_key("pepega"));
+        assert!(!token_client.token_store.tokens().broadcasters.contains_key("pepega"));
+    }
+
+    #[tokio::test]
+    async fn failed_check_doesnt_skip_the_rest() {
+        let mut token_client = test_token_client().await;
+        let token = refreshable_token("identity-token", "pepegabot");
+
+        token_client.token_store.set_identity_token("pepegabot", Option::Some(&token)).await.unwrap();
+        token_client.identity_tokens.insert("pepegabot".to_string(), token);
+
+        // App token can't be acquired and there's no user token, identity's one is still checked
+        let rejection = MockOAuthServer::start(failing_refresh(400, r#"{"status":400,"message":"Invalid refresh token"}"#)).await;
+        token_client.oauth_base_url = rejection.base_url.clone();
+
+        let error = TokenClient::check_tokens(&mut token_client).await.unwrap_err().to_string();
+
+        assert!(error.contains("Can't check user token"));
+        assert_eq!(error.split("; ").count(), 2);
+        assert!(!token_client.identity_tokens.contains_key("pepegabot"));
+    }
+}
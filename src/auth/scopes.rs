@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+use twitch_oauth2::Scope;
+
+/// Bot features that need permissions from Twitch
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Feature {
+    Chat,
+    Moderation,
+    Redemptions,
+    Polls,
+    Predictions,
+    Whispers,
+    /// Editing stream's title and game
+    Broadcast,
+}
+
+impl Feature {
+    /// Whether APIs of the feature only accept channel owner's token
+    pub fn requires_broadcaster(&self) -> bool {
+        matches!(self, Feature::Redemptions | Feature::Polls | Feature::Predictions | Feature::Broadcast)
+    }
+
+    pub fn scopes(&self) -> Vec<Scope> {
+        match self {
+            Feature::Chat => vec![Scope::ChatRead, Scope::ChatEdit],
+            Feature::Moderation => vec![
+                Scope::ChannelModerate,
+                Scope::ModerationRead,
+                Scope::parse("moderator:manage:banned_users"),
+                Scope::parse("moderator:manage:chat_messages"),
+            ],
+            Feature::Redemptions => vec![Scope::ChannelReadRedemptions, Scope::ChannelManageRedemptions],
+            Feature::Polls => vec![Scope::parse("channel:read:polls"), Scope::parse("channel:manage:polls")],
+            Feature::Predictions => vec![Scope::parse("channel:read:predictions"), Scope::parse("channel:manage:predictions")],
+            Feature::Whispers => vec![Scope::WhispersRead, Scope::WhispersEdit],
+            Feature::Broadcast => vec![Scope::ChannelManageBroadcast],
+        }
+    }
+}
+
+/// Features enabled unless configured otherwise
+pub fn default_features() -> Vec<Feature> {
+    vec![Feature::Chat]
+}
+
+fn push_unique(scopes: &mut Vec<Scope>, scope: Scope) {
+    if !scopes.contains(&scope) {
+        scopes.push(scope);
+    }
+}
+
+/// Scopes needed by given features, without duplicates
+pub fn required_scopes(features: &[Feature]) -> Vec<Scope> {
+    let mut scopes: Vec<Scope> = vec![];
+
+    for scope in features.iter().flat_map(|feature| feature.scopes()) {
+        push_unique(&mut scopes, scope);
+    }
+
+    scopes
+}
+
+/// Scopes to request, explicitly configured ones take precedence over the ones computed from features
+pub fn resolve_scopes(features: Option<&[Feature]>, overrides: Option<&[String]>) -> Vec<Scope> {
+    if let Some(overrides) = overrides {
+        let mut scopes: Vec<Scope> = vec![];
+
+        for scope in overrides {
+            push_unique(&mut scopes, Scope::parse(scope.clone()));
+        }
+
+        return scopes;
+    }
+
+    match features {
+        Some(features) => required_scopes(features),
+        None => required_scopes(&default_features()),
+    }
+}
+
+/// Required scopes the token wasn't granted
+pub fn missing_scopes(required: &[Scope], granted: &[Scope]) -> Vec<Scope> {
+    required.iter()
+        .filter(|scope| !granted.contains(scope))
+        .cloned()
+        .collect()
+}
+
+pub fn format_scopes(scopes: &[Scope]) -> String {
+    scopes.iter().map(|scope| scope.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use twitch_oauth2::Scope;
+
+    use super::{Feature, format_scopes, missing_scopes, required_scopes, resolve_scopes};
+
+    #[test]
+    fn required_scopes_works() {
+        let scopes = required_scopes(&[Feature::Chat, Feature::Moderation, Feature::Chat]);
+
+        assert_eq!(format_scopes(&scopes), "chat:read, chat:edit, channel:moderate, moderation:read, moderator:manage:banned_users, moderator:manage:chat_messages");
+    }
+
+    #[test]
+    fn resolve_scopes_prefers_overrides() {
+        assert_eq!(resolve_scopes(Option::None, Option::None), vec![Scope::ChatRead, Scope::ChatEdit]);
+        assert_eq!(resolve_scopes(Option::Some(&[Feature::Broadcast]), Option::None), vec![Scope::ChannelManageBroadcast]);
+
+        let overrides = vec!["chat:read".to_string(), "channel:manage:polls".to_string()];
+        assert_eq!(resolve_scopes(Option::Some(&[Feature::Chat]), Option::Some(&overrides)), vec![Scope::ChatRead, Scope::parse("channel:manage:polls")]);
+    }
+
+    #[test]
+    fn missing_scopes_works() {
+        let required = required_scopes(&[Feature::Chat, Feature::Whispers]);
+        let granted = vec![Scope::ChatRead, Scope::WhispersRead, Scope::UserReadEmail];
+
+        assert_eq!(format_scopes(&missing_scopes(&required, &granted)), "chat:edit, whispers:edit");
+    }
+}
@@ -0,0 +1,400 @@
+//! OAuth tokens live here instead of the user-authored config
+
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use openssl::symm::{Cipher, decrypt_aead, encrypt_aead};
+use serde::{Deserialize, Serialize};
+use twitch_oauth2::{AccessToken, AppAccessToken, RefreshToken, UserToken};
+
+use crate::config::{Config, TokenStoreBackend};
+use crate::database::repository::{Repositories, TokenRepository};
+
+/// Base64 encoded 32 byte key, tokens are stored encrypted with it
+pub const TOKEN_KEY_ENV: &str = "DEVELBOT_TOKEN_KEY";
+const DEFAULT_TOKEN_FILE: &str = "tokens.json";
+
+const ENCRYPTED_MAGIC: &[u8] = b"DVTK1";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct StoredToken {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+}
+
+impl StoredToken {
+    pub fn new(access_token: &AccessToken, refresh_token: Option<&RefreshToken>) -> Self {
+        Self {
+            access_token: access_token.secret().to_string(),
+            refresh_token: refresh_token.map(|refresh_token| refresh_token.secret().to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct StoredTokens {
+    pub app: Option<StoredToken>,
+    /// Bot account's token
+    pub user: Option<StoredToken>,
+    /// Additional bot identities' tokens by login
+    #[serde(default)]
+    pub identities: BTreeMap<String, StoredToken>,
+    /// Channel owners' tokens by channel login
+    #[serde(default)]
+    pub broadcasters: BTreeMap<String, StoredToken>,
+}
+
+/// AES-256-GCM encryption of the stored tokens
+pub struct TokenCipher {
+    key: [u8; KEY_LEN],
+}
+
+impl TokenCipher {
+    pub fn from_base64(value: &str) -> anyhow::Result<Self> {
+        let bytes = base64::decode(value.trim())
+            .map_err(|_| anyhow::anyhow!("Token key has to be base64 encoded"))?;
+
+        if bytes.len() != KEY_LEN {
+            return Err(anyhow::anyhow!("Token key has to be {} bytes long, got {}", KEY_LEN, bytes.len()));
+        }
+
+        let mut key = [0_u8; KEY_LEN];
+        key.copy_from_slice(&bytes);
+
+        Ok(Self {
+            key,
+        })
+    }
+
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        match std::env::var(TOKEN_KEY_ENV) {
+            Ok(value) if !value.is_empty() => Ok(Option::Some(TokenCipher::from_base64(value.as_str())?)),
+            _ => Ok(Option::None),
+        }
+    }
+
+    pub fn is_encrypted(data: &[u8]) -> bool {
+        data.starts_with(ENCRYPTED_MAGIC)
+    }
+
+    pub fn encrypt(&self, plain: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut nonce = [0_u8; NONCE_LEN];
+        openssl::rand::rand_bytes(&mut nonce)?;
+
+        let mut tag = [0_u8; TAG_LEN];
+        let encrypted = encrypt_aead(Cipher::aes_256_gcm(), &self.key, Option::Some(&nonce), ENCRYPTED_MAGIC, plain, &mut tag)?;
+
+        Ok([ENCRYPTED_MAGIC, &nonce, &tag, encrypted.as_slice()].concat())
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let data = data.strip_prefix(ENCRYPTED_MAGIC).ok_or_else(|| anyhow::anyhow!("Tokens aren't encrypted"))?;
+
+        if data.len() < NONCE_LEN + TAG_LEN {
+            return Err(anyhow::anyhow!("Encrypted tokens are truncated"));
+        }
+
+        let (nonce, rest) = data.split_at(NONCE_LEN);
+        let (tag, encrypted) = rest.split_at(TAG_LEN);
+
+        decrypt_aead(Cipher::aes_256_gcm(), &self.key, Option::Some(nonce), ENCRYPTED_MAGIC, encrypted, tag)
+            .map_err(|_| anyhow::anyhow!("Couldn't decrypt tokens, wrong {}?", TOKEN_KEY_ENV))
+    }
+}
+
+/// Token file readable and writable by the owner only, replaced atomically on every save
+pub struct FileTokenRepository {
+    path: PathBuf,
+}
+
+impl FileTokenRepository {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+        }
+    }
+
+    #[cfg(unix)]
+    fn create_private(path: &Path) -> std::io::Result<File> {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path)
+    }
+
+    #[cfg(not(unix))]
+    fn create_private(path: &Path) -> std::io::Result<File> {
+        OpenOptions::new().write(true).create(true).truncate(true).open(path)
+    }
+
+    #[cfg(unix)]
+    fn warn_if_exposed(&self, file: &File) {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Ok(metadata) = file.metadata() {
+            if metadata.permissions().mode() & 0o077 != 0 {
+                log::warn!("Token file '{}' is accessible by other users, it's going to be rewritten with owner-only permissions", self.path.display());
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn warn_if_exposed(&self, _file: &File) {}
+}
+
+#[async_trait]
+impl TokenRepository for FileTokenRepository {
+    async fn load_tokens(&self) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Option::None),
+            Err(error) => return Err(error.into()),
+        };
+
+        self.warn_if_exposed(&file);
+
+        let mut payload = vec![];
+        file.read_to_end(&mut payload)?;
+
+        Ok(Option::Some(payload))
+    }
+
+    async fn save_tokens(&self, payload: Vec<u8>) -> anyhow::Result<()> {
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(format!(".{}.tmp", std::process::id()));
+        let temp_path = PathBuf::from(temp_path);
+
+        let result = (|| -> std::io::Result<()> {
+            let mut file = FileTokenRepository::create_private(&temp_path)?;
+            file.write_all(&payload)?;
+            file.sync_all()?;
+
+            std::fs::rename(&temp_path, &self.path)
+        })();
+
+        if result.is_err() {
+            std::fs::remove_file(&temp_path).unwrap_or(());
+        }
+
+        Ok(result?)
+    }
+}
+
+/// Tokens cached in memory, every change is written through to the repository
+pub struct TokenStore {
+    cipher: Option<TokenCipher>,
+    repository: Arc<dyn TokenRepository>,
+    tokens: StoredTokens,
+}
+
+impl TokenStore {
+    pub async fn open(repository: Arc<dyn TokenRepository>, cipher: Option<TokenCipher>) -> anyhow::Result<Self> {
+        let tokens = match repository.load_tokens().await? {
+            Some(payload) if TokenCipher::is_encrypted(&payload) => {
+                let cipher = cipher.as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("Stored tokens are encrypted, set {} to read them", TOKEN_KEY_ENV))?;
+
+                serde_json::from_slice(&cipher.decrypt(&payload)?)?
+            },
+            Some(payload) => {
+                if cipher.is_some() {
+                    log::info!("Stored tokens aren't encrypted yet, they will be on the next save");
+                }
+
+                serde_json::from_slice(&payload)?
+            },
+            None => StoredTokens::default(),
+        };
+
+        if cipher.is_none() {
+            log::warn!("{} isn't set, tokens are stored unencrypted", TOKEN_KEY_ENV);
+        }
+
+        Ok(Self {
+            cipher,
+            repository,
+            tokens,
+        })
+    }
+
+    pub fn tokens(&self) -> &StoredTokens {
+        &self.tokens
+    }
+
+    async fn save(&self) -> anyhow::Result<()> {
+        let payload = serde_json::to_vec_pretty(&self.tokens)?;
+
+        let payload = match self.cipher.as_ref() {
+            Some(cipher) => cipher.encrypt(&payload)?,
+            None => payload,
+        };
+
+        self.repository.save_tokens(payload).await
+    }
+
+    pub async fn set_app_token(&mut self, token: &AppAccessToken) -> anyhow::Result<()> {
+        self.tokens.app = Option::Some(StoredToken::new(&token.access_token, token.refresh_token.as_ref()));
+
+        self.save().await
+    }
+
+    pub async fn set_user_token(&mut self, token: &UserToken) -> anyhow::Result<()> {
+        self.tokens.user = Option::Some(StoredToken::new(&token.access_token, token.refresh_token.as_ref()));
+
+        self.save().await
+    }
+
+    pub async fn remove_app_token(&mut self) -> anyhow::Result<()> {
+        self.tokens.app = Option::None;
+
+        self.save().await
+    }
+
+    pub async fn remove_user_token(&mut self) -> anyhow::Result<()> {
+        self.tokens.user = Option::None;
+
+        self.save().await
+    }
+
+    /// Stores broadcaster's token of the channel, `None` forgets it
+    pub async fn set_broadcaster_token(&mut self, channel: &str, token: Option<&UserToken>) -> anyhow::Result<()> {
+        match token {
+            Some(token) => self.tokens.broadcasters.insert(channel.to_lowercase(), StoredToken::new(&token.access_token, token.refresh_token.as_ref())),
+            None => self.tokens.broadcasters.remove(channel.to_lowercase().as_str()),
+        };
+
+        self.save().await
+    }
+
+    /// Stores token of additional bot identity, `None` forgets it
+    pub async fn set_identity_token(&mut self, login: &str, token: Option<&UserToken>) -> anyhow::Result<()> {
+        match token {
+            Some(token) => self.tokens.identities.insert(login.to_lowercase(), StoredToken::new(&token.access_token, token.refresh_token.as_ref())),
+            None => self.tokens.identities.remove(login.to_lowercase().as_str()),
+        };
+
+        self.save().await
+    }
+
+    /// Tokens that used to be kept in `config.toml` are moved into the store, unless it has some already
+    async fn import_legacy_tokens(&mut self, config: &Config) -> anyhow::Result<()> {
+        if self.tokens != StoredTokens::default() {
+            return Ok(());
+        }
+
+        let twitch_config = &config.app_config.twitch;
+        let legacy_token = |access_token: &Option<String>, refresh_token: &Option<String>| {
+            access_token.clone().filter(|token| !token.is_empty()).map(|access_token| StoredToken {
+                access_token,
+                refresh_token: refresh_token.clone().filter(|token| !token.is_empty()),
+            })
+        };
+
+        let tokens = StoredTokens {
+            app: legacy_token(&twitch_config.app_access_token, &twitch_config.app_refresh_token),
+            user: legacy_token(&twitch_config.user_access_token, &twitch_config.user_refresh_token),
+            identities: BTreeMap::new(),
+            broadcasters: BTreeMap::new(),
+        };
+
+        if tokens != StoredTokens::default() {
+            log::warn!("Moved tokens found in the config into the token store, they can be removed from the config now");
+
+            self.tokens = tokens;
+            self.save().await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Opens token store configured by `[token_store]` block, file next to the config by default
+pub async fn open_token_store(config: &Config, repositories: &Repositories) -> anyhow::Result<TokenStore> {
+    let token_store_config = &config.app_config.token_store;
+
+    let repository: Arc<dyn TokenRepository> = match token_store_config.backend {
+        TokenStoreBackend::File => {
+            let path = match token_store_config.path.as_ref() {
+                Some(path) => PathBuf::from(path),
+                None => Path::new(config.config_path.as_str()).with_file_name(DEFAULT_TOKEN_FILE),
+            };
+
+            log::debug!("Keeping tokens in '{}'", path.display());
+
+            Arc::new(FileTokenRepository::new(path))
+        },
+        TokenStoreBackend::Database => repositories.tokens.clone(),
+    };
+
+    let mut token_store = TokenStore::open(repository, TokenCipher::from_env()?).await?;
+    token_store.import_legacy_tokens(config).await?;
+
+    Ok(token_store)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::database::repository::memory::MemoryTokenRepository;
+    use crate::database::repository::TokenRepository;
+
+    use super::{FileTokenRepository, StoredToken, TokenCipher, TokenStore};
+
+    const KEY: &str = "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=";
+
+    #[test]
+    fn cipher_round_trip_works() {
+        let cipher = TokenCipher::from_base64(KEY).unwrap();
+        let encrypted = cipher.encrypt(b"secret").unwrap();
+
+        assert!(TokenCipher::is_encrypted(&encrypted));
+        assert!(!encrypted.windows(6).any(|window| window == b"secret"));
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), b"secret");
+
+        let other_cipher = TokenCipher::from_base64("ZmVkY2JhOTg3NjU0MzIxMGZlZGNiYTk4NzY1NDMyMTA=").unwrap();
+        assert!(other_cipher.decrypt(&encrypted).is_err());
+
+        assert!(TokenCipher::from_base64("c2hvcnQ=").is_err());
+    }
+
+    #[tokio::test]
+    async fn store_persists_encrypted_tokens() {
+        let repository = Arc::new(MemoryTokenRepository::default());
+
+        let mut token_store = TokenStore::open(repository.clone(), Option::Some(TokenCipher::from_base64(KEY).unwrap())).await.unwrap();
+        token_store.tokens.user = Option::Some(StoredToken { access_token: "user-token".to_string(), refresh_token: Option::None });
+        token_store.save().await.unwrap();
+
+        let payload = repository.load_tokens().await.unwrap().unwrap();
+        assert!(TokenCipher::is_encrypted(&payload));
+
+        assert!(TokenStore::open(repository.clone(), Option::None).await.is_err());
+
+        let token_store = TokenStore::open(repository, Option::Some(TokenCipher::from_base64(KEY).unwrap())).await.unwrap();
+        assert_eq!(token_store.tokens().user.as_ref().unwrap().access_token, "user-token");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn token_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("develbot-tokens-test-{}.json", std::process::id()));
+        let repository = FileTokenRepository::new(path.clone());
+
+        repository.save_tokens(b"{}".to_vec()).await.unwrap();
+        repository.save_tokens(b"{\"app\":null}".to_vec()).await.unwrap();
+
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(repository.load_tokens().await.unwrap().unwrap(), b"{\"app\":null}");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
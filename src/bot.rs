@@ -1,99 +1,156 @@
 use std::sync::Arc;
+use std::time::Instant;
 
 use clap::ArgMatches;
-use sqlx::PgPool;
 use tokio::sync::{mpsc::UnboundedReceiver, RwLock};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use twitch_api2::{helix::channels::ChannelInformation, TwitchClient};
-use twitch_irc::{ClientConfig, login::StaticLoginCredentials, message::ServerMessage, TwitchIRCClient, WSSTransport};
+use twitch_irc::{message::ServerMessage, TwitchIRCClient};
 use twitch_oauth2::{AppAccessToken, UserToken};
 
-use crate::auth::TokenClient;
+use crate::auth::{TokenClient, UserTokenCredentials};
+use crate::twitch::chat::ChatConnections;
 use crate::config::{ChannelInfo, Config};
+use crate::database::repository::Repositories;
+use crate::messages::cooldowns::CooldownTracker;
+use crate::messages::i18n::Localizer;
 use crate::messages::processor::MessageProcessor;
+use crate::shutdown::PendingTasks;
+use crate::supervisor::{BotState, BotSupervisor, restart_backoff, STABLE_AFTER};
+use crate::twitch::helix::HelixHttpClient;
+use crate::twitch::irc::IrcTransport;
 
-pub type TwitchChatClient = TwitchIRCClient<WSSTransport, StaticLoginCredentials>;
+pub type TwitchChatClient = TwitchIRCClient<IrcTransport, UserTokenCredentials>;
+
+/// Everything channels' bots share, so bots can be started and stopped after the config is reloaded
+#[derive(Clone)]
+pub struct BotContext {
+    pub args: Arc<RwLock<ArgMatches<'static>>>,
+    pub chat_connections: Arc<RwLock<ChatConnections>>,
+    pub config: Arc<RwLock<Config>>,
+    pub cooldowns: CooldownTracker,
+    pub localizer: Arc<Localizer>,
+    pub pending: PendingTasks,
+    pub repositories: Repositories,
+    /// Cancelled once the bot is asked to exit, bots stop taking new messages
+    pub shutdown: CancellationToken,
+    pub supervisor: BotSupervisor,
+    pub token_client: Arc<RwLock<TokenClient>>,
+}
+
+impl BotContext {
+    /// Runs channel's bot until the channel is parted or the bot shuts down, restarting it whenever it fails
+    pub fn spawn_bot(&self, channel_info: ChannelInfo) -> JoinHandle<()> {
+        let context = self.clone();
+        let channel = channel_info.channel.to_lowercase();
+        let stop = self.supervisor.register(channel.as_str(), &self.shutdown);
+
+        self.pending.spawn(async move {
+            let mut backoff = restart_backoff();
+
+            loop {
+                context.supervisor.set_state(channel.as_str(), BotState::Starting);
+
+                let started_at = Instant::now();
+                let result = context.run_bot(channel_info.clone(), stop.clone()).await;
+
+                if stop.is_cancelled() {
+                    break;
+                }
+
+                if started_at.elapsed() >= STABLE_AFTER {
+                    backoff.reset();
+                }
+
+                let error = match result {
+                    Ok(()) => "chat connection closed".to_string(),
+                    Err(error) => error.to_string(),
+                };
+                let delay = backoff.next_delay();
+
+                context.supervisor.set_state(channel.as_str(), BotState::Restarting {
+                    attempt: backoff.attempts(),
+                    backoff_sec: delay.as_secs(),
+                    error,
+                });
+
+                tokio::select! {
+                    _ = stop.cancelled() => break,
+                    _ = tokio::time::sleep(delay) => {},
+                }
+            }
+        })
+    }
+
+    async fn run_bot(&self, channel_info: ChannelInfo, stop: CancellationToken) -> anyhow::Result<()> {
+        let channel = channel_info.channel.clone();
+        let bot = Bot::<'static>::new(self, channel_info, stop).await?;
+
+        self.supervisor.set_state(channel.as_str(), BotState::Running);
+
+        bot.start_chat_processor().await
+    }
+
+    /// Parts the channel, its bot stops and isn't restarted
+    pub async fn stop_bot(&self, channel_info: &ChannelInfo) {
+        self.supervisor.unregister(channel_info.channel.as_str());
+
+        let identity = self.config.read().await.app_config.identity_of(channel_info);
+
+        self.chat_connections.write().await.unregister_channel(identity, channel_info.channel.as_str()).await;
+
+        log::info!("Stopped bot for channel '{}'", channel_info.channel.as_str());
+    }
+}
 
 // There's a lot of Arc+RwLock combos, should think if it's possible to reduce their amount
 // Otherwise they'll just keep piling up
 pub struct Bot<'a> {
-    pub args: Arc<RwLock<ArgMatches<'static>>>,
     pub channel_info: ChannelInfo,
     pub chat_client: Arc<RwLock<TwitchChatClient>>,
     pub chat_incoming_messages: Arc<RwLock<UnboundedReceiver<ServerMessage>>>,
-    pub config: Arc<RwLock<Config>>,
-    pub db_pool: Arc<RwLock<PgPool>>,
     pub message_processor: Arc<RwLock<MessageProcessor>>,
+    /// Cancelled when the channel is parted or the bot shuts down
+    pub stop: CancellationToken,
+    pub supervisor: BotSupervisor,
     pub token_client: Arc<RwLock<TokenClient>>,
-    pub twitch_client: TwitchClient<'a, reqwest::Client>,
+    pub twitch_client: TwitchClient<'a, HelixHttpClient>,
 }
 
 impl<'a> Bot<'a> {
-    pub async fn new(
-        args: Arc<RwLock<ArgMatches<'static>>>,
-        channel_info: ChannelInfo,
-        config: Arc<RwLock<Config>>,
-        db_pool: Arc<RwLock<PgPool>>,
-        token_client: Arc<RwLock<TokenClient>>
-    ) -> anyhow::Result<Bot<'a>> {
-        // Start token checker client
-        TokenClient::start(token_client.clone()).await?;
-
-        // Create Twitch chat IRC client
-        let (chat_client, chat_incoming_messages) = Bot::create_irc_client(config.clone(), token_client.clone()).await?;
-
-        let chat_client = Arc::new(RwLock::new(chat_client));
+    pub async fn new(context: &BotContext, channel_info: ChannelInfo, stop: CancellationToken) -> anyhow::Result<Bot<'a>> {
+        let config = context.config.clone();
+
+        // Channels of the same identity share its chat connection
+        let identity = config.read().await.app_config.identity_of(&channel_info);
+        let (chat_client, chat_incoming_messages) = context.chat_connections.write().await
+            .register_channel(identity.clone(), channel_info.channel.as_str()).await?;
+
         let chat_incoming_messages = Arc::new(RwLock::new(chat_incoming_messages));
 
         // Create message processor
-        let message_processor = MessageProcessor::new(chat_client.clone(), db_pool.clone());
+        let bot_name = config.read().await.app_config.twitch.bot_name.to_lowercase();
+        let bot_login = identity.clone().unwrap_or(bot_name);
+        let message_processor = MessageProcessor::new(context, bot_login, identity.clone(), chat_client.clone());
         let message_processor = Arc::new(RwLock::new(message_processor));
 
-        log::info!("Started bot for channel '{}'", channel_info.channel.as_str());
+        let helix_url = config.read().await.app_config.twitch.helix_url();
+
+        log::info!("Started bot for channel '{}' as identity '{}'", channel_info.channel.as_str(), identity.as_deref().unwrap_or("default"));
 
         Ok(Bot {
-            args: args.clone(),
             channel_info,
             chat_client,
             chat_incoming_messages,
-            config,
-            db_pool,
             message_processor,
-            token_client,
-            twitch_client: TwitchClient::<reqwest::Client>::default()
+            stop,
+            supervisor: context.supervisor.clone(),
+            token_client: context.token_client.clone(),
+            twitch_client: TwitchClient::with_client(HelixHttpClient::new(helix_url))
         })
     }
 
-    pub async fn create_irc_client(config: Arc<RwLock<Config>>, token_client: Arc<RwLock<TokenClient>>)
-        -> anyhow::Result<(TwitchChatClient, UnboundedReceiver<ServerMessage>)>
-    {
-        log::debug!("Starting bot's chat processor");
-
-        let bot_name = {
-            let config = config.read().await;
-
-            config.app_config.twitch.bot_name.clone()
-        };
-
-        let token_client_raw = token_client.read().await;
-        let bot_token = token_client_raw.user_token.clone().ok_or(anyhow::anyhow!("Can't start chat processor, no user token available"))?;
-        let bot_token = bot_token.access_token.secret().clone();
-
-        let client = {
-            let credentials = StaticLoginCredentials::new(bot_name, Option::Some(bot_token));
-            let chat_client_config = ClientConfig::new_simple(credentials);
-
-            let (incoming_messages, client) = TwitchChatClient::new(chat_client_config);
-
-            Ok((client, incoming_messages))
-        };
-
-        if client.is_err() {
-            return client.unwrap_err();
-        }
-
-        Ok(client.unwrap())
-    }
-
     // Returned data is immutable
     #[allow(dead_code)]
     pub async fn get_app_token(&'a self) -> anyhow::Result<AppAccessToken> {
@@ -132,18 +189,33 @@ impl<'a> Bot<'a> {
 
         let chat_incoming_messages = self.chat_incoming_messages.clone();
         let message_processor = self.message_processor.clone();
+        let stop = self.stop.clone();
+        let supervisor = self.supervisor.clone();
+        let channel = self.channel_info.channel.clone();
 
         let chat_task_handle = tokio::spawn(async move {
             let mut chat_incoming_messages = chat_incoming_messages.write().await;
             let message_processor = message_processor.clone();
 
-            while let Some(message) = chat_incoming_messages.recv().await {
+            loop {
+                // Message being processed is finished, the rest is left unprocessed on shutdown
+                let message = tokio::select! {
+                    message = chat_incoming_messages.recv() => message,
+                    _ = stop.cancelled() => break,
+                };
+
+                let message = match message {
+                    Some(message) => message,
+                    None => break,
+                };
+
+                supervisor.record_message(channel.as_str());
+
                 let message_processor = message_processor.read().await;
                 let result = message_processor.process_message(&message).await;
 
-                match result {
-                    Err(message) => log::error!("Failed to process message: {}", message),
-                    _ => {},
+                if let Err(error) = result {
+                    log::error!("Failed to process message: {}", error);
                 }
             }
         });
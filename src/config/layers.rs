@@ -0,0 +1,344 @@
+//! Config is merged from layers: defaults, the file, `DEVELBOT_*` env variables and CLI flags, later ones win
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use serde::de::DeserializeOwned;
+use toml::Value;
+use toml::value::Table;
+
+use crate::auth::token_store::TOKEN_KEY_ENV;
+
+pub const ENV_PREFIX: &str = "DEVELBOT_";
+/// Separates nested keys in env variable names, e.g. `DEVELBOT_DATABASE__HOST`
+const ENV_SEPARATOR: &str = "__";
+/// Shown instead of these values when the config is printed
+const SECRET_KEYS: [&str; 4] = ["database.password", "database.url", "global.pseudonym_key", "twitch.client_secret"];
+
+/// Where the effective value of a key comes from
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigSource {
+    Default,
+    File,
+    /// Env variable's name
+    Env(String),
+    /// CLI flag
+    Cli(String),
+}
+
+impl Display for ConfigSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File => write!(f, "file"),
+            ConfigSource::Env(name) => write!(f, "env {}", name),
+            ConfigSource::Cli(flag) => write!(f, "flag {}", flag),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ConfigLayers {
+    /// Merged value of all layers
+    pub value: Value,
+    /// Sources of the keys set by any layer, by key path like `channels[0].channel`
+    pub sources: BTreeMap<String, ConfigSource>,
+    /// Env/CLI values that were parsed as numbers or booleans, by key path, kept in case the key needs a string
+    raw_values: BTreeMap<String, (Vec<String>, String)>,
+}
+
+/// Parses env/CLI value as a TOML value, so numbers, booleans and arrays can be set, anything else is a string,
+/// `ConfigLayers::deserialize` falls back to the string for keys that expect one
+fn parse_value(raw: &str) -> Value {
+    toml::from_str::<Table>(format!("value = {}", raw).as_str()).ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(raw.to_string()))
+}
+
+/// Env variables' order, numeric segments are compared as numbers so `__10__` comes after `__2__`
+fn env_sort_key(name: &str) -> Vec<Result<usize, String>> {
+    name.split(ENV_SEPARATOR)
+        .map(|segment| segment.parse::<usize>().map_err(|_| segment.to_string()))
+        .collect()
+}
+
+fn path_of(segments: &[&str]) -> String {
+    let mut path = String::new();
+
+    for segment in segments {
+        if segment.parse::<usize>().is_ok() {
+            path.push_str(format!("[{}]", segment).as_str());
+        } else {
+            if !path.is_empty() {
+                path.push('.');
+            }
+
+            path.push_str(segment);
+        }
+    }
+
+    path
+}
+
+/// Sets the value at the path, creating missing tables, numeric segments index arrays of tables
+fn set_path(root: &mut Value, segments: &[&str], value: Value) -> anyhow::Result<()> {
+    let (last, parents) = segments.split_last().ok_or_else(|| anyhow::anyhow!("Empty key"))?;
+    let mut current = root;
+
+    for (position, segment) in parents.iter().enumerate() {
+        let next_is_index = segments[position + 1].parse::<usize>().is_ok();
+
+        current = match current {
+            Value::Table(table) => table.entry(segment.to_string())
+                .or_insert_with(|| if next_is_index { Value::Array(vec![]) } else { Value::Table(Table::new()) }),
+            Value::Array(array) => {
+                let index: usize = segment.parse().map_err(|_| anyhow::anyhow!("Expected index instead of '{}'", segment))?;
+
+                if index == array.len() {
+                    array.push(Value::Table(Table::new()));
+                }
+
+                array.get_mut(index).ok_or_else(|| anyhow::anyhow!("Index {} is past the end of '{}'", index, path_of(&segments[..position])))?
+            },
+            _ => return Err(anyhow::anyhow!("'{}' isn't a table", path_of(&segments[..position]))),
+        };
+    }
+
+    match current {
+        Value::Table(table) => {
+            table.insert(last.to_string(), value);
+            Ok(())
+        },
+        _ => Err(anyhow::anyhow!("'{}' isn't a table", path_of(parents))),
+    }
+}
+
+/// Every scalar of the value by its path, arrays of scalars are values themselves
+pub fn flatten(value: &Value, path: &str, leaves: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Table(table) => {
+            for (key, value) in table.iter() {
+                let key_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+
+                flatten(value, key_path.as_str(), leaves);
+            }
+        },
+        Value::Array(array) if array.iter().all(|value| value.is_table()) && !array.is_empty() => {
+            for (index, value) in array.iter().enumerate() {
+                flatten(value, format!("{}[{}]", path, index).as_str(), leaves);
+            }
+        },
+        _ => leaves.push((path.to_string(), value.clone())),
+    }
+}
+
+impl ConfigLayers {
+    pub fn new() -> Self {
+        Self {
+            value: Value::Table(Table::new()),
+            sources: BTreeMap::new(),
+            raw_values: BTreeMap::new(),
+        }
+    }
+
+    /// File layer replaces everything, it's always the first one
+    pub fn set_file(&mut self, value: Value) {
+        let mut leaves: Vec<(String, Value)> = vec![];
+        flatten(&value, "", &mut leaves);
+
+        for (path, _) in leaves {
+            self.sources.insert(path, ConfigSource::File);
+        }
+
+        self.value = value;
+    }
+
+    /// Sets a value by its dot separated key, e.g. `database.host` or `channels.0.channel`
+    pub fn set(&mut self, key: &str, raw_value: &str, source: ConfigSource) -> anyhow::Result<()> {
+        let segments: Vec<&str> = key.split('.').collect();
+        let value = parse_value(raw_value);
+        let path = path_of(&segments);
+
+        if matches!(value, Value::Integer(_) | Value::Float(_) | Value::Boolean(_) | Value::Datetime(_)) {
+            self.raw_values.insert(path.clone(), (segments.iter().map(|segment| segment.to_string()).collect(), raw_value.to_string()));
+        } else {
+            self.raw_values.remove(&path);
+        }
+
+        set_path(&mut self.value, &segments, value)
+            .map_err(|error| anyhow::anyhow!("Can't set '{}' from {}: {}", key, source, error))?;
+
+        self.sources.insert(path, source);
+
+        Ok(())
+    }
+
+    /// Applies `DEVELBOT_*` variables, `__` separates nested keys, e.g. `DEVELBOT_TWITCH__CLIENT_SECRET`
+    pub fn apply_env(&mut self, vars: impl Iterator<Item = (String, String)>) -> anyhow::Result<()> {
+        let mut vars: Vec<(String, String)> = vars
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX) && name != TOKEN_KEY_ENV)
+            .collect();
+        // Array items have to be added in order
+        vars.sort_by_key(|(name, _)| env_sort_key(name));
+
+        for (name, raw_value) in vars {
+            let key = name[ENV_PREFIX.len()..].to_lowercase().replace(ENV_SEPARATOR, ".");
+
+            self.set(key.as_str(), raw_value.as_str(), ConfigSource::Env(name.clone()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Merged value as `T`, env/CLI values parsed as numbers or booleans are retried as strings where `T` expects one,
+    /// e.g. an all-digit client secret
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, toml::de::Error> {
+        let mut value = self.value.clone();
+        let mut raw_values = self.raw_values.clone();
+
+        loop {
+            let error = match serde_path_to_error::deserialize(value.clone()) {
+                Ok(deserialized) => return Ok(deserialized),
+                Err(error) => error,
+            };
+
+            let (segments, raw_value) = match raw_values.remove(error.path().to_string().as_str()) {
+                Some(raw) => raw,
+                None => return Err(error.into_inner()),
+            };
+
+            let segments: Vec<&str> = segments.iter().map(|segment| segment.as_str()).collect();
+
+            if set_path(&mut value, &segments, Value::String(raw_value)).is_err() {
+                return Err(error.into_inner());
+            }
+        }
+    }
+
+    /// Source of the effective value at the path, keys set by no layer have their default values
+    pub fn source_of(&self, path: &str) -> ConfigSource {
+        self.sources.get(path).cloned().unwrap_or(ConfigSource::Default)
+    }
+
+    /// Effective config, one `key = value # source` per line, secrets are hidden
+    pub fn describe(&self, effective: &Value) -> String {
+        let mut leaves: Vec<(String, Value)> = vec![];
+        flatten(effective, "", &mut leaves);
+
+        leaves.iter()
+            .map(|(path, value)| {
+                let value = if SECRET_KEYS.contains(&path.as_str()) { "\"***\"".to_string() } else { value.to_string() };
+
+                format!("{} = {} # {}", path, value, self.source_of(path.as_str()))
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use toml::Value;
+
+    use super::{ConfigLayers, ConfigSource};
+
+    fn vars(vars: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect::<Vec<(String, String)>>().into_iter()
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let mut layers = ConfigLayers::new();
+        layers.set_file(toml::from_str("[database]\nhost = 'localhost'\nport = 5432\n\n[[channels]]\nchannel = 'pepega'").unwrap());
+
+        layers.apply_env(vars(&[
+            ("DEVELBOT_DATABASE__HOST", "db"),
+            ("DEVELBOT_DATABASE__PORT", "6432"),
+            ("DEVELBOT_CHANNELS__1__CHANNEL", "forsen"),
+            ("DEVELBOT_TWITCH__FEATURES", "[\"chat\", \"polls\"]"),
+            ("DEVELBOT_TOKEN_KEY", "secret"),
+            ("HOME", "/root"),
+        ])).unwrap();
+        layers.set("database.host", "cli-db", ConfigSource::Cli("--set".to_string())).unwrap();
+
+        let expected: Value = toml::from_str(r#"
+            [database]
+            host = "cli-db"
+            port = 6432
+
+            [twitch]
+            features = ["chat", "polls"]
+
+            [[channels]]
+            channel = "pepega"
+
+            [[channels]]
+            channel = "forsen"
+        "#).unwrap();
+
+        assert_eq!(layers.value, expected);
+        assert_eq!(layers.source_of("database.host"), ConfigSource::Cli("--set".to_string()));
+        assert_eq!(layers.source_of("database.port"), ConfigSource::Env("DEVELBOT_DATABASE__PORT".to_string()));
+        assert_eq!(layers.source_of("channels[0].channel"), ConfigSource::File);
+        assert_eq!(layers.source_of("channels[1].channel"), ConfigSource::Env("DEVELBOT_CHANNELS__1__CHANNEL".to_string()));
+        assert_eq!(layers.source_of("global.login_flow"), ConfigSource::Default);
+    }
+
+    #[test]
+    fn describe_hides_secrets() {
+        let mut layers = ConfigLayers::new();
+        layers.apply_env(vars(&[("DEVELBOT_TWITCH__CLIENT_SECRET", "hunter2"), ("DEVELBOT_TWITCH__BOT_NAME", "develbot")])).unwrap();
+
+        assert_eq!(
+            layers.describe(&layers.value),
+            "twitch.bot_name = \"develbot\" # env DEVELBOT_TWITCH__BOT_NAME\ntwitch.client_secret = \"***\" # env DEVELBOT_TWITCH__CLIENT_SECRET"
+        );
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Twitch {
+        client_secret: String,
+        check_every_sec: u64,
+        bot_name: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        twitch: Twitch,
+    }
+
+    #[test]
+    fn strings_are_kept_where_expected() {
+        let mut layers = ConfigLayers::new();
+        layers.apply_env(vars(&[
+            ("DEVELBOT_TWITCH__CLIENT_SECRET", "0123456789"),
+            ("DEVELBOT_TWITCH__CHECK_EVERY_SEC", "30"),
+            ("DEVELBOT_TWITCH__BOT_NAME", "true"),
+        ])).unwrap();
+
+        let config: Config = layers.deserialize().unwrap();
+
+        assert_eq!(config.twitch, Twitch { client_secret: "0123456789".to_string(), check_every_sec: 30, bot_name: Option::Some("true".to_string()) });
+
+        layers.set("twitch.check_every_sec", "soon", ConfigSource::Cli("--set".to_string())).unwrap();
+        assert!(layers.deserialize::<Config>().is_err());
+    }
+
+    #[test]
+    fn env_array_items_are_ordered_numerically() {
+        let mut env: Vec<(String, String)> = (0..12)
+            .map(|index| (format!("DEVELBOT_CHANNELS__{}__CHANNEL", index), format!("channel{}", index)))
+            .collect();
+        env.reverse();
+
+        let mut layers = ConfigLayers::new();
+        layers.apply_env(env.into_iter()).unwrap();
+
+        let channels = layers.value["channels"].as_array().unwrap();
+
+        assert_eq!(channels.len(), 12);
+        assert_eq!(channels[10]["channel"].as_str(), Option::Some("channel10"));
+        assert_eq!(layers.source_of("channels[2].channel"), ConfigSource::Env("DEVELBOT_CHANNELS__2__CHANNEL".to_string()));
+    }
+}
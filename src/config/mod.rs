@@ -0,0 +1,344 @@
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use twitch_api2::TWITCH_HELIX_URL;
+use twitch_oauth2::Scope;
+
+use crate::auth::http_client::TWITCH_OAUTH_BASE_URL;
+use crate::auth::scopes::{default_features, Feature, required_scopes, resolve_scopes};
+use crate::config::layers::{ConfigLayers, ConfigSource};
+use crate::config::settings::{ChannelSettings, ChannelSettingsConfig};
+use crate::config::validation::{parse_app_config, parse_file};
+use crate::twitch::irc::TWITCH_IRC_URL;
+
+pub mod layers;
+pub mod reload;
+pub mod settings;
+pub mod validation;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoginFlow {
+    /// Browser redirects back to `auth_host:auth_port`
+    #[default]
+    Redirect,
+    /// Code is entered at twitch.tv/activate, nothing has to reach the bot
+    Device,
+}
+
+impl FromStr for LoginFlow {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "redirect" => Ok(LoginFlow::Redirect),
+            "device" => Ok(LoginFlow::Device),
+            _ => Err(anyhow::anyhow!("Unknown login flow '{}', expected one of: redirect, device", value)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GlobalConfig {
+    pub auth_host: String,
+    pub auth_port: u64,
+    /// How long to wait for the login in browser, 5 minutes by default
+    pub auth_timeout_sec: Option<u64>,
+    /// How often the config file is checked for changes, 30 seconds by default, 0 only reloads on SIGHUP
+    pub config_check_every_sec: Option<u64>,
+    /// Address of `/healthz` and `/readyz`, `127.0.0.1` by default
+    pub health_host: Option<String>,
+    /// 8098 by default, 0 disables the endpoints
+    pub health_port: Option<u64>,
+    #[serde(default)]
+    pub login_flow: LoginFlow,
+    /// Secret the pseudonyms of chatters are derived from, needed by `retention.pseudonymize_after_days`,
+    /// changing it gives already pseudonymized chatters new pseudonyms
+    pub pseudonym_key: Option<String>,
+    pub retention_check_every_sec: Option<u64>,
+    pub retention_chunk_size: Option<i64>,
+    /// How long finishing pending work may take on SIGINT/SIGTERM, 10 seconds by default
+    pub shutdown_timeout_sec: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseBackend {
+    /// Nothing is persisted between restarts
+    Memory,
+    #[default]
+    Postgres,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DatabaseConfig {
+    #[serde(default)]
+    pub backend: DatabaseBackend,
+    /// Full connection URL, replaces separate connection properties below
+    pub url: Option<String>,
+    pub socket: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub database: Option<String>,
+    pub max_connections: Option<u32>,
+    pub connect_timeout_sec: Option<u64>,
+    pub statement_timeout_ms: Option<u64>,
+    /// One of: disable, allow, prefer, require, verify-ca, verify-full
+    pub ssl_mode: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TwitchConfig {
+    /// Legacy, tokens are imported into the token store once and can be removed from the config
+    #[serde(skip_serializing)]
+    pub app_access_token: Option<String>,
+    #[serde(skip_serializing)]
+    pub app_refresh_token: Option<String>,
+    pub bot_name: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub check_every_sec: Option<u64>,
+    /// Features the bot needs permissions for, only chat by default
+    pub features: Option<Vec<Feature>>,
+    /// Additional accounts the bot can speak as, channels pick one with `identity`
+    #[serde(default)]
+    pub identities: Vec<IdentityConfig>,
+    /// Scopes requested for user token, replaces the ones computed from `features`
+    pub scopes: Option<Vec<String>>,
+    /// Overrides of Twitch endpoints, for pointing the bot at a local mock
+    #[serde(default)]
+    pub urls: TwitchUrlsConfig,
+    #[serde(skip_serializing)]
+    pub user_access_token: Option<String>,
+    #[serde(skip_serializing)]
+    pub user_refresh_token: Option<String>,
+}
+
+impl TwitchConfig {
+    pub fn oauth_url(&self) -> String {
+        self.urls.oauth.clone().unwrap_or_else(|| TWITCH_OAUTH_BASE_URL.to_string())
+    }
+
+    pub fn helix_url(&self) -> String {
+        self.urls.helix.clone().unwrap_or_else(|| TWITCH_HELIX_URL.to_string())
+    }
+
+    pub fn irc_url(&self) -> String {
+        self.urls.irc.clone().unwrap_or_else(|| TWITCH_IRC_URL.to_string())
+    }
+
+    fn enabled_features(&self) -> Vec<Feature> {
+        self.features.clone().unwrap_or_else(default_features)
+    }
+
+    /// Scopes bot's user token has to have for enabled features
+    pub fn required_scopes(&self) -> Vec<Scope> {
+        let features: Vec<Feature> = self.enabled_features().into_iter()
+            .filter(|feature| !feature.requires_broadcaster())
+            .collect();
+
+        resolve_scopes(Option::Some(&features), self.scopes.as_deref())
+    }
+
+    /// Scopes broadcasters' tokens have to have for enabled features, none if no feature needs them
+    pub fn broadcaster_scopes(&self) -> Vec<Scope> {
+        let features: Vec<Feature> = self.enabled_features().into_iter()
+            .filter(|feature| feature.requires_broadcaster())
+            .collect();
+
+        required_scopes(&features)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IdentityConfig {
+    /// Account's login
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TwitchUrlsConfig {
+    /// `https://id.twitch.tv/oauth2` by default
+    pub oauth: Option<String>,
+    /// `https://api.twitch.tv/helix/` by default
+    pub helix: Option<String>,
+    /// Chat over WebSocket, `wss://irc-ws.chat.twitch.tv` by default
+    pub irc: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RetentionConfig {
+    /// Messages older than that are removed from the logs
+    pub max_age_days: Option<u32>,
+    /// Only that many latest messages are kept in the logs
+    pub max_rows: Option<i64>,
+    /// Chatter logins of messages older than that are replaced with pseudonyms
+    pub pseudonymize_after_days: Option<u32>,
+    /// Move removed messages to `chat_logs_archive` instead of deleting them
+    #[serde(default)]
+    pub archive: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChannelInfo {
+    pub admin: String,
+    pub channel: String,
+    /// Login of the identity the bot speaks as in this channel, `bot_name` by default
+    pub identity: Option<String>,
+    pub retention: Option<RetentionConfig>,
+    /// Overrides of `[channel_defaults]`
+    #[serde(default)]
+    pub settings: ChannelSettingsConfig,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenStoreBackend {
+    /// JSON file, encrypted if `DEVELBOT_TOKEN_KEY` is set
+    #[default]
+    File,
+    /// `token_blobs` table of the configured database
+    Database,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct TokenStoreConfig {
+    #[serde(default)]
+    pub backend: TokenStoreBackend,
+    /// Token file, `tokens.json` next to the config by default
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AppConfig {
+    /// Settings every channel inherits
+    #[serde(default)]
+    pub channel_defaults: ChannelSettingsConfig,
+    pub channels: Vec<ChannelInfo>,
+    pub global: GlobalConfig,
+    pub database: DatabaseConfig,
+    #[serde(default)]
+    pub token_store: TokenStoreConfig,
+    pub twitch: TwitchConfig,
+}
+
+impl AppConfig {
+    /// Logins of additional identities, the default one is `twitch.bot_name`
+    pub fn identity_names(&self) -> Vec<String> {
+        self.twitch.identities.iter()
+            .map(|identity| identity.name.to_lowercase())
+            .filter(|name| !name.eq_ignore_ascii_case(self.twitch.bot_name.as_str()))
+            .collect()
+    }
+
+    /// Additional identity the channel is mapped to, `None` for the default one
+    pub fn identity_of(&self, channel_info: &ChannelInfo) -> Option<String> {
+        channel_info.identity.as_ref()
+            .map(|identity| identity.to_lowercase())
+            .filter(|identity| !identity.eq_ignore_ascii_case(self.twitch.bot_name.as_str()))
+    }
+
+    /// Effective settings of the channel, the defaults ones for channels that aren't configured
+    pub fn settings_of(&self, channel: &str) -> ChannelSettings {
+        let channel_settings = self.channels.iter()
+            .find(|channel_info| channel_info.channel.eq_ignore_ascii_case(channel))
+            .map(|channel_info| channel_info.settings.clone())
+            .unwrap_or_default();
+
+        ChannelSettings::resolve(&channel_settings, &self.channel_defaults)
+    }
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub app_config: AppConfig,
+    pub config_path: String,
+}
+
+impl Config {
+    /// Merges the file, env variables and CLI flags, returns the layers along with the file's contents
+    fn load_layers(args: &ArgMatches<'static>, config_path: &str) -> anyhow::Result<(ConfigLayers, String)> {
+        let mut layers = ConfigLayers::new();
+        let mut config_contents = String::new();
+
+        match File::open(config_path) {
+            Ok(mut config_file) => {
+                config_file.read_to_string(&mut config_contents)?;
+
+                layers.set_file(parse_file(config_path, config_contents.as_str())?);
+            },
+            // Everything can come from env variables in containers
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                log::warn!("Config file '{}' not found, only env variables and flags are used", config_path);
+            },
+            Err(error) => return Err(error.into()),
+        }
+
+        layers.apply_env(std::env::vars())?;
+
+        if let Some(values) = args.values_of("set") {
+            for value in values {
+                let (key, raw_value) = value.split_once('=')
+                    .ok_or_else(|| anyhow::anyhow!("Expected KEY=VALUE in --set, got '{}'", value))?;
+
+                layers.set(key.trim(), raw_value.trim(), ConfigSource::Cli("--set".to_string()))?;
+            }
+        }
+
+        if let Some(login_flow) = args.value_of("login-flow") {
+            layers.set("global.login_flow", format!("\"{}\"", login_flow).as_str(), ConfigSource::Cli("--login-flow".to_string()))?;
+        }
+
+        Ok((layers, config_contents))
+    }
+
+    fn read_app_config(args: &ArgMatches<'static>, config_path: &str) -> anyhow::Result<(AppConfig, ConfigLayers)> {
+        let (layers, config_contents) = Config::load_layers(args, config_path)?;
+        let app_config = parse_app_config(config_path, config_contents.as_str(), &layers)?;
+
+        Ok((app_config, layers))
+    }
+
+    /// Validates the config without starting anything, every problem is reported in the error
+    pub async fn check(args: Arc<RwLock<ArgMatches<'static>>>) -> anyhow::Result<()> {
+        let args = args.read().await;
+        let config_path = args.value_of("app-config").unwrap().to_string(); // Safe unwrap, because we provided the default value at arg def
+
+        Config::read_app_config(&args, config_path.as_str())?;
+
+        println!("Config '{}' is valid", config_path);
+
+        Ok(())
+    }
+
+    /// Prints effective config with the source of every value
+    pub async fn show(args: Arc<RwLock<ArgMatches<'static>>>) -> anyhow::Result<()> {
+        let args = args.read().await;
+        let config_path = args.value_of("app-config").unwrap().to_string(); // Safe unwrap, because we provided the default value at arg def
+
+        let (app_config, layers) = Config::read_app_config(&args, config_path.as_str())?;
+
+        println!("{}", layers.describe(&toml::Value::try_from(&app_config)?));
+
+        Ok(())
+    }
+
+    pub async fn from_args(args: Arc<RwLock<ArgMatches<'static>>>) -> anyhow::Result<Config> {
+        let args = args.read().await;
+        let config_path = args.value_of("app-config").unwrap().to_string(); // Safe unwrap, because we provided the default value at arg def
+        let (app_config, _) = Config::read_app_config(&args, config_path.as_str())?;
+
+        Ok(Config {
+            app_config,
+            config_path
+        })
+    }
+
+}
@@ -0,0 +1,267 @@
+//! Reloads the config on SIGHUP or when the file changes, applies what can be applied without restart
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::{Duration, SystemTime};
+
+use tokio::signal::unix::{signal, SignalKind};
+use toml::Value;
+
+use crate::bot::BotContext;
+use crate::config::{AppConfig, ChannelInfo, Config};
+use crate::config::layers::flatten;
+
+const DEFAULT_CHECK_EVERY_SEC: u64 = 30;
+/// Top-level keys applied live, changes of anything else need a restart
+const LIVE_KEYS: [&str; 2] = ["channel_defaults", "channels"];
+
+/// What changed between the current and the reloaded config
+#[derive(Debug, Default)]
+pub struct ConfigDiff {
+    pub joined: Vec<ChannelInfo>,
+    pub parted: Vec<ChannelInfo>,
+    /// Channels which settings changed, bots pick them up from the config
+    pub updated: Vec<String>,
+    /// Keys which changes aren't applied until restart
+    pub restart_required: Vec<String>,
+}
+
+fn is_live(path: &str) -> bool {
+    LIVE_KEYS.iter().any(|key| {
+        path.strip_prefix(key)
+            .map(|rest| rest.is_empty() || rest.starts_with('.') || rest.starts_with('['))
+            .unwrap_or(false)
+    })
+}
+
+fn leaves_of(app_config: &AppConfig) -> anyhow::Result<BTreeMap<String, Value>> {
+    let mut leaves: Vec<(String, Value)> = vec![];
+    flatten(&Value::try_from(app_config)?, "", &mut leaves);
+
+    Ok(leaves.into_iter().collect())
+}
+
+impl ConfigDiff {
+    pub fn between(current: &AppConfig, reloaded: &AppConfig) -> anyhow::Result<ConfigDiff> {
+        let mut diff = ConfigDiff::default();
+
+        let current_channels: HashMap<String, &ChannelInfo> = current.channels.iter()
+            .map(|channel_info| (channel_info.channel.to_lowercase(), channel_info))
+            .collect();
+        let reloaded_channels: HashMap<String, &ChannelInfo> = reloaded.channels.iter()
+            .map(|channel_info| (channel_info.channel.to_lowercase(), channel_info))
+            .collect();
+
+        let defaults_changed = Value::try_from(&current.channel_defaults)? != Value::try_from(&reloaded.channel_defaults)?;
+
+        for channel_info in current.channels.iter() {
+            let reloaded_channel = reloaded_channels.get(&channel_info.channel.to_lowercase());
+
+            // Channel moved to another identity has to be joined by its connection
+            let is_kept = reloaded_channel
+                .map(|reloaded_channel| reloaded.identity_of(reloaded_channel) == current.identity_of(channel_info))
+                .unwrap_or(false);
+
+            if !is_kept {
+                diff.parted.push(channel_info.clone());
+            } else if defaults_changed || Value::try_from(channel_info)? != Value::try_from(*reloaded_channel.unwrap())? {
+                diff.updated.push(channel_info.channel.to_lowercase());
+            }
+        }
+
+        for channel_info in reloaded.channels.iter() {
+            let current_channel = current_channels.get(&channel_info.channel.to_lowercase());
+
+            let is_kept = current_channel
+                .map(|current_channel| current.identity_of(current_channel) == reloaded.identity_of(channel_info))
+                .unwrap_or(false);
+
+            if !is_kept {
+                diff.joined.push(channel_info.clone());
+            }
+        }
+
+        let current_leaves = leaves_of(current)?;
+        let reloaded_leaves = leaves_of(reloaded)?;
+        let paths: BTreeSet<&String> = current_leaves.keys().chain(reloaded_leaves.keys()).collect();
+
+        diff.restart_required = paths.into_iter()
+            .filter(|path| !is_live(path.as_str()))
+            .filter(|path| current_leaves.get(*path) != reloaded_leaves.get(*path))
+            .cloned()
+            .collect();
+
+        Ok(diff)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.joined.is_empty() && self.parted.is_empty() && self.updated.is_empty() && self.restart_required.is_empty()
+    }
+}
+
+fn channel_names(channels: &[ChannelInfo]) -> String {
+    channels.iter().map(|channel_info| channel_info.channel.as_str()).collect::<Vec<&str>>().join(", ")
+}
+
+/// Reads and validates the config again, live keys replace current ones, the rest waits for restart
+async fn reload_config(context: &BotContext) -> anyhow::Result<ConfigDiff> {
+    let config_path = context.config.read().await.config_path.clone();
+    let (reloaded, _) = Config::read_app_config(&*context.args.read().await, config_path.as_str())?;
+
+    let mut config = context.config.write().await;
+    let diff = ConfigDiff::between(&config.app_config, &reloaded)?;
+
+    config.app_config.channel_defaults = reloaded.channel_defaults;
+    config.app_config.channels = reloaded.channels;
+
+    Ok(diff)
+}
+
+async fn apply_reload(context: &BotContext) {
+    let diff = match reload_config(context).await {
+        Ok(diff) => diff,
+        Err(error) => {
+            log::error!("Config isn't reloaded, the current one is kept: {}", error);
+            return;
+        },
+    };
+
+    if diff.is_empty() {
+        log::info!("Config reloaded, nothing changed");
+        return;
+    }
+
+    for channel_info in diff.parted.iter() {
+        context.stop_bot(channel_info).await;
+    }
+
+    for channel_info in diff.joined.iter() {
+        context.spawn_bot(channel_info.clone());
+    }
+
+    if !diff.parted.is_empty() {
+        log::info!("Config reloaded, parted channels: {}", channel_names(&diff.parted));
+    }
+
+    if !diff.joined.is_empty() {
+        log::info!("Config reloaded, joined channels: {}", channel_names(&diff.joined));
+    }
+
+    if !diff.updated.is_empty() {
+        log::info!("Config reloaded, updated settings of channels: {}", diff.updated.join(", "));
+    }
+
+    if !diff.restart_required.is_empty() {
+        log::warn!("Config reloaded, changes of these keys need a restart: {}", diff.restart_required.join(", "));
+    }
+}
+
+fn modified_at(config_path: &str) -> Option<SystemTime> {
+    std::fs::metadata(config_path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Spawns background task reloading the config on SIGHUP and when the file's modification time changes
+pub async fn start_config_reload(context: BotContext) -> anyhow::Result<()> {
+    let mut hangups = signal(SignalKind::hangup())?;
+
+    let (config_path, check_every_sec) = {
+        let config = context.config.read().await;
+
+        (config.config_path.clone(), config.app_config.global.config_check_every_sec.unwrap_or(DEFAULT_CHECK_EVERY_SEC))
+    };
+
+    tokio::spawn(async move {
+        let mut modified = modified_at(config_path.as_str());
+        // Only SIGHUP triggers reloads when checks are disabled
+        let mut interval = tokio::time::interval(Duration::from_secs(check_every_sec.max(1)));
+
+        loop {
+            tokio::select! {
+                _ = context.shutdown.cancelled() => break,
+                _ = hangups.recv() => {
+                    log::info!("Got SIGHUP, reloading config");
+                },
+                _ = interval.tick() => {
+                    if check_every_sec == 0 || modified_at(config_path.as_str()) == modified {
+                        continue;
+                    }
+
+                    log::info!("Config file '{}' changed, reloading", config_path);
+                },
+            }
+
+            modified = modified_at(config_path.as_str());
+
+            apply_reload(&context).await;
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::AppConfig;
+
+    use super::ConfigDiff;
+
+    fn app_config(channels: &str, database_host: &str) -> AppConfig {
+        toml::from_str(format!(r#"
+            {}
+
+            [global]
+            auth_host = "localhost"
+            auth_port = 8099
+
+            [database]
+            host = "{}"
+
+            [twitch]
+            bot_name = "develbot"
+            client_id = "id"
+            client_secret = "secret"
+
+            [[twitch.identities]]
+            name = "pepegabot"
+        "#, channels, database_host).as_str()).unwrap()
+    }
+
+    #[test]
+    fn diff_works() {
+        let current = app_config(r#"
+            [[channels]]
+            admin = "forsen"
+            channel = "forsen"
+
+            [[channels]]
+            admin = "pepega"
+            channel = "pepega"
+
+            [[channels]]
+            admin = "xqc"
+            channel = "xqc"
+        "#, "localhost");
+        let reloaded = app_config(r#"
+            [[channels]]
+            admin = "forsen"
+            channel = "Forsen"
+            identity = "pepegabot"
+
+            [[channels]]
+            admin = "nymn"
+            channel = "pepega"
+
+            [[channels]]
+            admin = "lirik"
+            channel = "lirik"
+        "#, "db");
+
+        let diff = ConfigDiff::between(&current, &reloaded).unwrap();
+        let channels = |channels: &Vec<crate::config::ChannelInfo>| channels.iter().map(|channel_info| channel_info.channel.clone()).collect::<Vec<String>>();
+
+        assert_eq!(channels(&diff.parted), vec!["forsen", "xqc"]);
+        assert_eq!(channels(&diff.joined), vec!["Forsen", "lirik"]);
+        assert_eq!(diff.updated, vec!["pepega"]);
+        assert_eq!(diff.restart_required, vec!["database.host"]);
+        assert!(ConfigDiff::between(&current, &current).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+//! Per-channel settings, every channel inherits `[channel_defaults]` and overrides it in `[channels.settings]`
+
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PREFIX: &str = "~";
+pub const DEFAULT_LOCALE: &str = "en";
+pub const DEFAULT_GREETING: &str = "Hej";
+pub const DEFAULT_GREETING_EVERY_SEC: u64 = 3600;
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ModerationFilters {
+    /// Messages containing any of them are removed, case insensitive
+    #[serde(default)]
+    pub blocked_words: Vec<String>,
+    /// Longer messages are removed
+    pub max_length: Option<usize>,
+    /// Authors of removed messages are timed out for that long, their messages are only deleted otherwise
+    pub timeout_sec: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Cooldowns {
+    /// Between uses of the same command in the channel
+    pub command_sec: Option<u64>,
+    /// Between commands of the same chatter
+    pub user_sec: Option<u64>,
+}
+
+/// Settings as they're written in the config, unset ones are inherited
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ChannelSettingsConfig {
+    /// Commands start with it, `~` by default
+    pub prefix: Option<String>,
+    /// Language of bot's replies, `en` by default
+    pub locale: Option<String>,
+    /// IANA name like `Europe/Warsaw`, `UTC` by default
+    pub timezone: Option<String>,
+    /// Slugs of enabled commands, every command by default
+    pub commands: Option<Vec<String>>,
+    /// Sent when the bot joins the channel, `{channel}` and `{bot}` are replaced with logins, `Hej` by default, empty to stay silent
+    pub greeting: Option<String>,
+    /// Least time between greetings, so reconnects don't repeat them, 1 hour by default
+    pub greeting_every_sec: Option<u64>,
+    /// Only greet when the channel is live
+    pub greet_only_live: Option<bool>,
+    /// Replaces the whole default block
+    pub filters: Option<ModerationFilters>,
+    /// Replaces the whole default block
+    pub cooldowns: Option<Cooldowns>,
+}
+
+/// Effective settings of a channel
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelSettings {
+    pub prefix: String,
+    pub locale: String,
+    pub timezone: Tz,
+    pub commands: Option<Vec<String>>,
+    pub greeting: Option<String>,
+    pub greeting_every_sec: u64,
+    pub greet_only_live: bool,
+    pub filters: ModerationFilters,
+    pub cooldowns: Cooldowns,
+}
+
+impl ChannelSettings {
+    /// Channel's own settings win over the defaults, built-in values are used if neither has one
+    pub fn resolve(channel: &ChannelSettingsConfig, defaults: &ChannelSettingsConfig) -> Self {
+        let timezone = channel.timezone.as_ref().or(defaults.timezone.as_ref())
+            .and_then(|timezone| match timezone.parse::<Tz>() {
+                Ok(timezone) => Option::Some(timezone),
+                Err(_) => {
+                    log::warn!("Unknown timezone '{}', UTC is used instead", timezone);
+                    Option::None
+                },
+            })
+            .unwrap_or(Tz::UTC);
+
+        Self {
+            prefix: channel.prefix.clone().or_else(|| defaults.prefix.clone()).unwrap_or_else(|| DEFAULT_PREFIX.to_string()),
+            locale: channel.locale.clone().or_else(|| defaults.locale.clone()).unwrap_or_else(|| DEFAULT_LOCALE.to_string()),
+            timezone,
+            commands: channel.commands.clone().or_else(|| defaults.commands.clone()),
+            greeting: channel.greeting.clone().or_else(|| defaults.greeting.clone())
+                .or_else(|| Option::Some(DEFAULT_GREETING.to_string()))
+                .filter(|greeting| !greeting.trim().is_empty()),
+            greeting_every_sec: channel.greeting_every_sec.or(defaults.greeting_every_sec).unwrap_or(DEFAULT_GREETING_EVERY_SEC),
+            greet_only_live: channel.greet_only_live.or(defaults.greet_only_live).unwrap_or(false),
+            filters: channel.filters.clone().or_else(|| defaults.filters.clone()).unwrap_or_default(),
+            cooldowns: channel.cooldowns.clone().or_else(|| defaults.cooldowns.clone()).unwrap_or_default(),
+        }
+    }
+
+    pub fn is_command_enabled(&self, slug: &str) -> bool {
+        self.commands.as_ref()
+            .map(|commands| commands.iter().any(|command| command.eq_ignore_ascii_case(slug)))
+            .unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Tz;
+
+    use super::{ChannelSettings, ChannelSettingsConfig, Cooldowns};
+
+    #[test]
+    fn channel_settings_inherit_defaults() {
+        let defaults: ChannelSettingsConfig = toml::from_str(r#"
+            prefix = "!"
+            timezone = "Europe/Warsaw"
+            commands = ["hello", "time"]
+
+            [cooldowns]
+            command_sec = 5
+            user_sec = 10
+        "#).unwrap();
+        let channel: ChannelSettingsConfig = toml::from_str(r#"
+            locale = "pl"
+            greeting = ""
+
+            [cooldowns]
+            user_sec = 30
+        "#).unwrap();
+
+        let settings = ChannelSettings::resolve(&channel, &defaults);
+
+        assert_eq!(settings.prefix, "!");
+        assert_eq!(settings.locale, "pl");
+        assert_eq!(settings.timezone, Tz::Europe__Warsaw);
+        assert_eq!(settings.greeting, Option::None);
+        assert_eq!(settings.cooldowns, Cooldowns { command_sec: Option::None, user_sec: Option::Some(30) });
+        assert!(settings.is_command_enabled("Hello"));
+        assert!(!settings.is_command_enabled("logs"));
+
+        let builtin = ChannelSettings::resolve(&ChannelSettingsConfig::default(), &ChannelSettingsConfig::default());
+
+        assert_eq!(builtin.prefix, "~");
+        assert_eq!(builtin.timezone, Tz::UTC);
+        assert_eq!(builtin.greeting.as_deref(), Option::Some("Hej"));
+        assert!(builtin.is_command_enabled("logs"));
+    }
+}
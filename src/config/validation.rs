@@ -0,0 +1,453 @@
+//! Checks the whole config at once, so every problem can be fixed in one go
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use chrono_tz::Tz;
+use toml::Value;
+use twitch_oauth2::Scope;
+
+use crate::config::{AppConfig, DatabaseBackend, TokenStoreBackend};
+use crate::config::layers::{ConfigLayers, ConfigSource};
+use crate::config::settings::ChannelSettingsConfig;
+use crate::messages::core::Command;
+use crate::messages::i18n::{is_supported, locale_names};
+use crate::messages::processor::MessageProcessor;
+
+/// Tokens used to be kept in the config, they're imported into the token store instead of being reported
+const LEGACY_KEYS: [&str; 4] = ["twitch.app_access_token", "twitch.app_refresh_token", "twitch.user_access_token", "twitch.user_refresh_token"];
+const SSL_MODES: [&str; 6] = ["disable", "allow", "prefer", "require", "verify-ca", "verify-full"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigProblem {
+    /// Key path, e.g. `channels[1].channel`
+    pub path: String,
+    /// `file:line` of the key, or env variable/flag it was set with, if it could be found
+    pub location: Option<String>,
+    pub message: String,
+}
+
+/// Every problem found in the config file
+#[derive(Debug)]
+pub struct ConfigError {
+    pub file: String,
+    pub problems: Vec<ConfigProblem>,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Config '{}' has {} problem(s):", self.file, self.problems.len())?;
+
+        for problem in self.problems.iter() {
+            match problem.location.as_ref() {
+                Some(location) => write!(f, "\n  {}: {}: {}", location, problem.path, problem.message)?,
+                None => write!(f, "\n  {}: {}", problem.path, problem.message)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Lines of tables and keys by their path, good enough for configs written one key per line
+fn index_lines(source: &str) -> HashMap<String, usize> {
+    let mut lines: HashMap<String, usize> = HashMap::new();
+    let mut array_lengths: HashMap<String, usize> = HashMap::new();
+    let mut table = String::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+
+        if let Some(name) = line.strip_prefix("[[").and_then(|line| line.split("]]").next()) {
+            let name = name.trim();
+            let length = array_lengths.entry(name.to_string()).or_insert(0);
+
+            table = format!("{}[{}]", name, length);
+            *length += 1;
+        } else if let Some(name) = line.strip_prefix('[').and_then(|line| line.split(']').next()) {
+            let name = name.trim();
+
+            // Sub-table of the latest array item, e.g. `[channels.retention]`
+            table = array_lengths.iter()
+                .filter(|(array, _)| name.starts_with(format!("{}.", array).as_str()))
+                .max_by_key(|(array, _)| array.len())
+                .map(|(array, length)| format!("{}[{}]{}", array, length - 1, &name[array.len()..]))
+                .unwrap_or_else(|| name.to_string());
+        } else if let Some((key, _)) = line.split_once('=') {
+            let key = key.trim().trim_matches(|c| c == '"' || c == '\'');
+
+            if key.is_empty() || key.starts_with('#') {
+                continue;
+            }
+
+            lines.insert(join_path(table.as_str(), key), index + 1);
+            continue;
+        } else {
+            continue;
+        }
+
+        lines.insert(table.clone(), index + 1);
+    }
+
+    lines
+}
+
+fn join_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+/// Keys of `source` that didn't make it through deserialization, so nothing reads them
+fn find_unknown_keys(source: &Value, known: &Value, path: &str, unknown: &mut Vec<String>) {
+    match (source, known) {
+        (Value::Table(source), Value::Table(known)) => {
+            for (key, value) in source.iter() {
+                let key_path = join_path(path, key);
+
+                match known.get(key) {
+                    Some(known_value) => find_unknown_keys(value, known_value, key_path.as_str(), unknown),
+                    None if LEGACY_KEYS.contains(&key_path.as_str()) => {},
+                    None => unknown.push(key_path),
+                }
+            }
+        },
+        (Value::Array(source), Value::Array(known)) => {
+            for (index, (value, known_value)) in source.iter().zip(known.iter()).enumerate() {
+                find_unknown_keys(value, known_value, format!("{}[{}]", path, index).as_str(), unknown);
+            }
+        },
+        _ => {},
+    }
+}
+
+struct Problems<'a> {
+    file: &'a str,
+    layers: &'a ConfigLayers,
+    lines: HashMap<String, usize>,
+    problems: Vec<ConfigProblem>,
+}
+
+impl<'a> Problems<'a> {
+    fn location(&self, path: &str) -> Option<String> {
+        match self.layers.source_of(path) {
+            source @ ConfigSource::Env(_) | source @ ConfigSource::Cli(_) => return Option::Some(source.to_string()),
+            ConfigSource::Default | ConfigSource::File => {},
+        }
+
+        // Missing keys are reported at their table
+        self.lines.get(path).copied()
+            .or_else(|| path.rsplit_once('.').and_then(|(table, _)| self.lines.get(table).copied()))
+            .map(|line| format!("{}:{}", self.file, line))
+    }
+
+    fn add(&mut self, path: &str, message: String) {
+        self.problems.push(ConfigProblem {
+            path: path.to_string(),
+            location: self.location(path),
+            message,
+        });
+    }
+
+    fn into_error(self) -> ConfigError {
+        ConfigError {
+            file: self.file.to_string(),
+            problems: self.problems,
+        }
+    }
+}
+
+fn check_database(app_config: &AppConfig, problems: &mut Problems) {
+    let database = &app_config.database;
+
+    if database.backend == DatabaseBackend::Postgres && database.url.is_none() && std::env::var("DATABASE_URL").is_err() {
+        if database.socket.is_none() {
+            if database.host.is_none() {
+                problems.add("database.host", "Expected either 'host' and 'port', 'socket' or 'url'".to_string());
+            }
+
+            if database.port.is_none() {
+                problems.add("database.port", "Expected either 'host' and 'port', 'socket' or 'url'".to_string());
+            }
+
+            if database.database.is_none() {
+                problems.add("database.database", "Expected database name".to_string());
+            }
+        }
+
+        if database.username.is_none() {
+            problems.add("database.username", "Expected 'username' unless 'url' is set".to_string());
+        }
+
+        if database.password.is_none() {
+            problems.add("database.password", "Expected 'password' unless 'url' is set".to_string());
+        }
+    }
+
+    if let Some(ssl_mode) = database.ssl_mode.as_ref() {
+        if !SSL_MODES.contains(&ssl_mode.as_str()) {
+            problems.add("database.ssl_mode", format!("Unknown value '{}', expected one of: {}", ssl_mode, SSL_MODES.join(", ")));
+        }
+    }
+
+    if app_config.token_store.backend == TokenStoreBackend::Database && database.backend == DatabaseBackend::Memory {
+        problems.add("token_store.backend", "Tokens can't be kept in the database, database backend is 'memory'".to_string());
+    }
+}
+
+fn check_channels(app_config: &AppConfig, problems: &mut Problems) {
+    if app_config.channels.is_empty() {
+        problems.add("channels", "Expected at least one [[channels]] block".to_string());
+    }
+
+    let identities = app_config.identity_names();
+    let mut seen_channels: HashMap<String, usize> = HashMap::new();
+
+    for (index, channel_info) in app_config.channels.iter().enumerate() {
+        let channel = channel_info.channel.to_lowercase();
+
+        if channel.is_empty() {
+            problems.add(format!("channels[{}].channel", index).as_str(), "Channel can't be empty".to_string());
+        }
+
+        match seen_channels.get(channel.as_str()) {
+            Some(first_index) => {
+                problems.add(format!("channels[{}].channel", index).as_str(), format!("Channel '{}' is already configured in channels[{}]", channel, first_index));
+            },
+            None => {
+                seen_channels.insert(channel, index);
+            },
+        }
+
+        if let Some(identity) = app_config.identity_of(channel_info) {
+            if !identities.contains(&identity) {
+                problems.add(format!("channels[{}].identity", index).as_str(), format!("Identity '{}' isn't listed in [[twitch.identities]]", identity));
+            }
+        }
+
+        let pseudonymizes = channel_info.retention.as_ref().map_or(false, |retention| retention.pseudonymize_after_days.is_some());
+
+        if pseudonymizes && app_config.global.pseudonym_key.as_deref().map_or(true, str::is_empty) {
+            problems.add(format!("channels[{}].retention.pseudonymize_after_days", index).as_str(), "Pseudonymization needs global.pseudonym_key".to_string());
+        }
+
+        check_settings(&channel_info.settings, format!("channels[{}].settings", index).as_str(), problems);
+    }
+
+    check_settings(&app_config.channel_defaults, "channel_defaults", problems);
+}
+
+fn check_settings(settings: &ChannelSettingsConfig, path: &str, problems: &mut Problems) {
+    if let Some(prefix) = settings.prefix.as_ref() {
+        if prefix.is_empty() || prefix.contains(char::is_whitespace) {
+            problems.add(format!("{}.prefix", path).as_str(), "Prefix can't be empty or contain spaces".to_string());
+        }
+    }
+
+    if let Some(locale) = settings.locale.as_ref() {
+        if !is_supported(locale) {
+            problems.add(format!("{}.locale", path).as_str(), format!("Unsupported locale '{}', expected any of: {}", locale, locale_names()));
+        }
+    }
+
+    if let Some(timezone) = settings.timezone.as_ref() {
+        if timezone.parse::<Tz>().is_err() {
+            problems.add(format!("{}.timezone", path).as_str(), format!("Unknown timezone '{}', expected IANA name like 'Europe/Warsaw'", timezone));
+        }
+    }
+
+    let commands = MessageProcessor::get_commands();
+    let slugs: Vec<&str> = commands.iter()
+        .map(|command| command.get_command_info().get_slug())
+        .collect();
+
+    for command in settings.commands.iter().flatten() {
+        let name = command.to_lowercase();
+
+        // Aliases enable their commands too
+        if !commands.iter().any(|known| known.get_command_info().matches(name.as_str())) {
+            problems.add(format!("{}.commands", path).as_str(), format!("Unknown command '{}', expected any of: {}", command, slugs.join(", ")));
+        }
+    }
+}
+
+fn check_twitch(app_config: &AppConfig, problems: &mut Problems) {
+    if !(1..=65535).contains(&app_config.global.auth_port) {
+        problems.add("global.auth_port", format!("Port {} is out of range 1-65535", app_config.global.auth_port));
+    }
+
+    if let Some(health_port) = app_config.global.health_port {
+        if health_port > 65535 {
+            problems.add("global.health_port", format!("Port {} is out of range 0-65535", health_port));
+        }
+    }
+
+    for (key, value) in [("bot_name", &app_config.twitch.bot_name), ("client_id", &app_config.twitch.client_id), ("client_secret", &app_config.twitch.client_secret)] {
+        if value.is_empty() {
+            problems.add(format!("twitch.{}", key).as_str(), "Can't be empty".to_string());
+        }
+    }
+
+    for scope in app_config.twitch.scopes.iter().flatten() {
+        if let Scope::Other(_) = Scope::parse(scope.clone()) {
+            problems.add("twitch.scopes", format!("Unknown scope '{}'", scope));
+        }
+    }
+}
+
+/// Parses the config file into the file layer
+pub fn parse_file(file: &str, source: &str) -> Result<Value, ConfigError> {
+    toml::from_str::<Value>(source).map_err(|error| ConfigError {
+        file: file.to_string(),
+        problems: vec![ConfigProblem {
+            path: "-".to_string(),
+            location: error.line_col().map(|(line, _)| format!("{}:{}", file, line + 1)),
+            message: error.to_string(),
+        }],
+    })
+}
+
+/// Deserializes merged layers and validates the result, `file` and `source` are the config file's name and contents
+pub fn parse_app_config(file: &str, source: &str, layers: &ConfigLayers) -> Result<AppConfig, ConfigError> {
+    let mut problems = Problems {
+        file,
+        layers,
+        lines: index_lines(source),
+        problems: vec![],
+    };
+
+    let app_config: AppConfig = match layers.deserialize() {
+        Ok(app_config) => app_config,
+        Err(error) => {
+            // Merged value has no lines, the file alone might point at the problem
+            let location = toml::from_str::<AppConfig>(source).err()
+                .and_then(|error| error.line_col())
+                .map(|(line, _)| format!("{}:{}", file, line + 1));
+
+            problems.problems.push(ConfigProblem {
+                path: "-".to_string(),
+                location,
+                message: error.to_string(),
+            });
+
+            return Err(problems.into_error());
+        },
+    };
+
+    if let Ok(known_value) = Value::try_from(&app_config) {
+        let mut unknown: Vec<String> = vec![];
+        find_unknown_keys(&layers.value, &known_value, "", &mut unknown);
+
+        for path in unknown {
+            problems.add(path.as_str(), "Unknown key".to_string());
+        }
+    }
+
+    check_channels(&app_config, &mut problems);
+    check_database(&app_config, &mut problems);
+    check_twitch(&app_config, &mut problems);
+
+    if problems.problems.is_empty() {
+        Ok(app_config)
+    } else {
+        Err(problems.into_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::layers::ConfigLayers;
+
+    use super::{ConfigError, ConfigProblem, index_lines, parse_app_config, parse_file};
+
+    const VALID_CONFIG: &str = include_str!("../../configs/config.example.toml");
+
+    fn problem(path: &str, location: &str, message: &str) -> ConfigProblem {
+        ConfigProblem {
+            path: path.to_string(),
+            location: Option::Some(location.to_string()),
+            message: message.to_string(),
+        }
+    }
+
+    fn parse(source: &str, env: &[(&str, &str)]) -> Result<(), ConfigError> {
+        let mut layers = ConfigLayers::new();
+        layers.set_file(parse_file("config.toml", source)?);
+        layers.apply_env(env.iter().map(|(name, value)| (name.to_string(), value.to_string()))).unwrap();
+
+        parse_app_config("config.toml", source, &layers).map(|_| ())
+    }
+
+    #[test]
+    fn example_config_is_valid() {
+        parse(VALID_CONFIG, &[]).unwrap();
+    }
+
+    #[test]
+    fn index_lines_works() {
+        let lines = index_lines("[[channels]]\nchannel = 'a'\n[[channels]]\nchannel = 'b'\n[channels.retention]\nmax_rows = 1\n[global]\n\"auth_port\" = 1");
+
+        assert_eq!(lines["channels[1]"], 3);
+        assert_eq!(lines["channels[1].channel"], 4);
+        assert_eq!(lines["channels[1].retention.max_rows"], 6);
+        assert_eq!(lines["global.auth_port"], 8);
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let source = r#"
+[[channels]]
+admin = "forsenCD"
+channel = "pepega"
+
+[[channels]]
+admin = "forsenCD"
+channel = "Pepega"
+colour = "red"
+
+[channels.settings]
+locale = "tlh"
+timezone = "Mars/Olympus"
+commands = ["current", "Hello", "nope"]
+
+[global]
+auth_host = "localhost"
+auth_port = 70000
+
+[database]
+username = "develbot"
+password = "develbot"
+
+[twitch]
+bot_name = "develbot"
+client_id = "client-id"
+client_secret = "client-secret"
+"#;
+
+        let error = parse(source, &[("DEVELBOT_DATABASE__PORT", "6432"), ("DEVELBOT_DATABASE__SSL_MODE", "maybe")]).unwrap_err();
+
+        assert_eq!(error.problems, vec![
+            problem("channels[1].colour", "config.toml:9", "Unknown key"),
+            problem("channels[1].channel", "config.toml:8", "Channel 'pepega' is already configured in channels[0]"),
+            problem("channels[1].settings.locale", "config.toml:12", "Unsupported locale 'tlh', expected any of: de, en, pl"),
+            problem("channels[1].settings.timezone", "config.toml:13", "Unknown timezone 'Mars/Olympus', expected IANA name like 'Europe/Warsaw'"),
+            problem("channels[1].settings.commands", "config.toml:14", "Unknown command 'nope', expected any of: hello, firstmessage, forgetme, lastseen, logs, search, setlang, settz, status, time"),
+            problem("database.host", "config.toml:20", "Expected either 'host' and 'port', 'socket' or 'url'"),
+            problem("database.database", "config.toml:20", "Expected database name"),
+            problem("database.ssl_mode", "env DEVELBOT_DATABASE__SSL_MODE", "Unknown value 'maybe', expected one of: disable, allow, prefer, require, verify-ca, verify-full"),
+            problem("global.auth_port", "config.toml:18", "Port 70000 is out of range 1-65535"),
+        ]);
+        assert!(error.to_string().contains("config.toml:18: global.auth_port: Port 70000 is out of range 1-65535"));
+    }
+
+    #[test]
+    fn syntax_error_has_line() {
+        let error = parse_file("config.toml", "[global]\nauth_port = \n").unwrap_err();
+
+        assert_eq!(error.problems[0].location.as_deref(), Option::Some("config.toml:2"));
+    }
+}
@@ -1,9 +1,14 @@
 use chrono::prelude::*;
+use futures::stream::BoxStream;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
 use sqlx::{FromRow, PgPool};
 
 #[derive(Clone, Debug, FromRow)]
 pub struct ChatLogMessage {
     pub id: Option<i32>,
+    pub channel_login: String,
     pub chatter_login: String,
     pub message: String,
     pub posted_at: DateTime<Utc>,
@@ -11,12 +16,12 @@ pub struct ChatLogMessage {
 }
 
 impl ChatLogMessage {
-    const CURRENT_VERSION: i16 = 1_i16;
+    const CURRENT_VERSION: i16 = 2_i16;
 
-    #[allow(dead_code)]
-    pub fn new(chatter_login: String, message: String, posted_at: DateTime<Utc>) -> Self {
+    pub fn new(channel_login: String, chatter_login: String, message: String, posted_at: DateTime<Utc>) -> Self {
         Self {
             id: Option::None,
+            channel_login,
             chatter_login,
             message,
             posted_at,
@@ -28,6 +33,33 @@ impl ChatLogMessage {
         sqlx::query("\
             CREATE TABLE IF NOT EXISTS chat_logs (\
                 id SERIAL PRIMARY KEY,\
+                channel_login varchar(255),\
+                chatter_login varchar(255),\
+                message varchar(255),\
+                posted_at timestamptz,\
+                version smallint\
+            );\
+        ").execute(pool).await?;
+
+        // Version 1 rows were logged without the channel they came from
+        sqlx::query("ALTER TABLE chat_logs ADD COLUMN IF NOT EXISTS channel_login varchar(255);")
+            .execute(pool)
+            .await?;
+
+        sqlx::query("\
+            CREATE INDEX IF NOT EXISTS chat_logs_channel_chatter_idx \
+            ON chat_logs (channel_login, chatter_login, posted_at);\
+        ").execute(pool).await?;
+
+        sqlx::query("\
+            CREATE INDEX IF NOT EXISTS chat_logs_message_search_idx \
+            ON chat_logs USING GIN (to_tsvector('simple', message));\
+        ").execute(pool).await?;
+
+        sqlx::query("\
+            CREATE TABLE IF NOT EXISTS chat_logs_archive (\
+                id integer PRIMARY KEY,\
+                channel_login varchar(255),\
                 chatter_login varchar(255),\
                 message varchar(255),\
                 posted_at timestamptz,\
@@ -38,12 +70,12 @@ impl ChatLogMessage {
         Ok(())
     }
 
-    #[allow(dead_code)]
     pub async fn insert(pool: &PgPool, chat_log_message: Self) -> anyhow::Result<()> {
         sqlx::query("\
-            INSERT INTO chat_logs (chatter_login, message, posted_at, version) \
-            VALUES ($1, $2, $3, $4)\
+            INSERT INTO chat_logs (channel_login, chatter_login, message, posted_at, version) \
+            VALUES ($1, $2, $3, $4, $5)\
         ")
+            .bind(chat_log_message.channel_login)
             .bind(chat_log_message.chatter_login)
             .bind(chat_log_message.message)
             .bind(chat_log_message.posted_at)
@@ -53,4 +85,234 @@ impl ChatLogMessage {
 
         Ok(())
     }
+
+    /// Latest messages of a chatter in a channel, newest first
+    pub async fn find_by_chatter(pool: &PgPool, channel_login: &str, chatter_login: &str, limit: i64, offset: i64) -> anyhow::Result<Vec<Self>> {
+        let result = sqlx::query_as::<_, ChatLogMessage>("\
+            SELECT * FROM chat_logs \
+            WHERE channel_login = $1 AND chatter_login = $2 \
+            ORDER BY posted_at DESC \
+            LIMIT $3 OFFSET $4\
+        ")
+            .bind(channel_login)
+            .bind(chatter_login)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    pub async fn find_first_by_chatter(pool: &PgPool, channel_login: &str, chatter_login: &str) -> anyhow::Result<Option<Self>> {
+        let result = sqlx::query_as::<_, ChatLogMessage>("\
+            SELECT * FROM chat_logs \
+            WHERE channel_login = $1 AND chatter_login = $2 \
+            ORDER BY posted_at ASC \
+            LIMIT 1\
+        ")
+            .bind(channel_login)
+            .bind(chatter_login)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    pub async fn find_last_by_chatter(pool: &PgPool, channel_login: &str, chatter_login: &str) -> anyhow::Result<Option<Self>> {
+        let result = sqlx::query_as::<_, ChatLogMessage>("\
+            SELECT * FROM chat_logs \
+            WHERE channel_login = $1 AND chatter_login = $2 \
+            ORDER BY posted_at DESC \
+            LIMIT 1\
+        ")
+            .bind(channel_login)
+            .bind(chatter_login)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Full-text search over channel's messages, newest first
+    pub async fn search(pool: &PgPool, channel_login: &str, text: &str, limit: i64, offset: i64) -> anyhow::Result<Vec<Self>> {
+        let result = sqlx::query_as::<_, ChatLogMessage>("\
+            SELECT * FROM chat_logs \
+            WHERE channel_login = $1 \
+            AND to_tsvector('simple', message) @@ plainto_tsquery('simple', $2) \
+            ORDER BY posted_at DESC \
+            LIMIT $3 OFFSET $4\
+        ")
+            .bind(channel_login)
+            .bind(text)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Streams channel's messages in chronological order, optionally limited to `[from, to)` range
+    pub fn stream_by_channel<'a>(pool: &'a PgPool, channel_login: &'a str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> BoxStream<'a, Result<Self, sqlx::Error>> {
+        sqlx::query_as::<_, ChatLogMessage>("\
+            SELECT * FROM chat_logs \
+            WHERE channel_login = $1 \
+            AND ($2::timestamptz IS NULL OR posted_at >= $2) \
+            AND ($3::timestamptz IS NULL OR posted_at < $3) \
+            ORDER BY posted_at ASC, id ASC\
+        ")
+            .bind(channel_login)
+            .bind(from)
+            .bind(to)
+            .fetch(pool)
+    }
+
+    /// Removes a chunk of channel's messages posted before `before`, returns amount of removed rows
+    pub async fn remove_older_than(pool: &PgPool, channel_login: &str, before: DateTime<Utc>, chunk_size: i64, archive: bool) -> anyhow::Result<u64> {
+        let selection = "\
+            SELECT id FROM chat_logs \
+            WHERE channel_login = $1 AND posted_at < $2 \
+            LIMIT $3\
+        ";
+
+        let result = sqlx::query(ChatLogMessage::removal_query(selection, archive).as_str())
+            .bind(channel_login)
+            .bind(before)
+            .bind(chunk_size)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Removes a chunk of channel's messages that don't fit into latest `max_rows`, returns amount of removed rows
+    pub async fn remove_over_limit(pool: &PgPool, channel_login: &str, max_rows: i64, chunk_size: i64, archive: bool) -> anyhow::Result<u64> {
+        let selection = "\
+            SELECT id FROM chat_logs \
+            WHERE channel_login = $1 \
+            ORDER BY posted_at DESC \
+            OFFSET $2 LIMIT $3\
+        ";
+
+        let result = sqlx::query(ChatLogMessage::removal_query(selection, archive).as_str())
+            .bind(channel_login)
+            .bind(max_rows)
+            .bind(chunk_size)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    fn removal_query(selection: &str, archive: bool) -> String {
+        if archive {
+            format!("\
+                WITH moved AS (\
+                    DELETE FROM chat_logs WHERE id IN ({}) \
+                    RETURNING id, channel_login, chatter_login, message, posted_at, version\
+                ) \
+                INSERT INTO chat_logs_archive (id, channel_login, chatter_login, message, posted_at, version) \
+                SELECT id, channel_login, chatter_login, message, posted_at, version FROM moved\
+            ", selection)
+        } else {
+            format!("DELETE FROM chat_logs WHERE id IN ({})", selection)
+        }
+    }
+
+    /// Stable pseudonym of the login, HMAC-SHA256 keyed with `global.pseudonym_key`, so it can't be reversed
+    /// by hashing known logins, every backend has to use it
+    pub fn pseudonym(key: &[u8], login: &str) -> anyhow::Result<String> {
+        let key = PKey::hmac(key)?;
+        let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+        signer.update(login.as_bytes())?;
+
+        let digest: String = signer.sign_to_vec()?.iter().take(6).map(|byte| format!("{:02x}", byte)).collect();
+
+        // Twitch logins can't contain dashes, so pseudonyms never collide with real logins
+        Ok(format!("anon-{}", digest))
+    }
+
+    /// Replaces chatter logins of a chunk of channel's messages posted before `before` with pseudonyms keyed with `key`,
+    /// archived ones included, returns amount of updated rows
+    pub async fn pseudonymize_older_than(pool: &PgPool, channel_login: &str, before: DateTime<Utc>, chunk_size: i64, key: &[u8]) -> anyhow::Result<u64> {
+        let mut updated = 0_u64;
+
+        for table in ["chat_logs", "chat_logs_archive"].iter() {
+            // Archive gets what's left of the chunk, so a partial chunk still means there's nothing more to do
+            let limit = chunk_size - updated as i64;
+
+            if limit <= 0 {
+                break;
+            }
+
+            let mut transaction = pool.begin().await?;
+
+            let rows: Vec<(i32, String)> = sqlx::query_as(format!("\
+                SELECT id, chatter_login FROM {} \
+                WHERE channel_login = $1 AND posted_at < $2 AND chatter_login NOT LIKE 'anon-%' \
+                LIMIT $3 \
+                FOR UPDATE\
+            ", table).as_str())
+                .bind(channel_login)
+                .bind(before)
+                .bind(limit)
+                .fetch_all(&mut transaction)
+                .await?;
+
+            // Pseudonyms are computed here rather than in SQL, so they match the memory backend's
+            let mut ids: Vec<i32> = vec![];
+            let mut pseudonyms: Vec<String> = vec![];
+
+            for (id, chatter_login) in rows {
+                ids.push(id);
+                pseudonyms.push(ChatLogMessage::pseudonym(key, chatter_login.as_str())?);
+            }
+
+            let result = sqlx::query(format!("\
+                UPDATE {table} SET chatter_login = pseudonyms.chatter_login \
+                FROM (SELECT unnest($1::integer[]) AS id, unnest($2::varchar[]) AS chatter_login) AS pseudonyms \
+                WHERE {table}.id = pseudonyms.id\
+            ", table = table).as_str())
+                .bind(ids)
+                .bind(pseudonyms)
+                .execute(&mut transaction)
+                .await?;
+
+            transaction.commit().await?;
+
+            updated += result.rows_affected();
+        }
+
+        Ok(updated)
+    }
+
+    /// Deletes every logged message of a chatter in a channel, archived ones included
+    pub async fn delete_by_chatter(pool: &PgPool, channel_login: &str, chatter_login: &str) -> anyhow::Result<u64> {
+        let mut deleted = 0_u64;
+
+        for table in ["chat_logs", "chat_logs_archive"].iter() {
+            let result = sqlx::query(format!("DELETE FROM {} WHERE channel_login = $1 AND chatter_login = $2", table).as_str())
+                .bind(channel_login)
+                .bind(chatter_login)
+                .execute(pool)
+                .await?;
+
+            deleted += result.rows_affected();
+        }
+
+        Ok(deleted)
+    }
+
+    /// Short single-line representation, suitable for replying in chat
+    pub fn to_chat_line(&self, max_message_len: usize) -> String {
+        let message = if self.message.chars().count() > max_message_len {
+            let truncated: String = self.message.chars().take(max_message_len).collect();
+            format!("{}…", truncated)
+        } else {
+            self.message.clone()
+        };
+
+        format!("[{}] <{}> {}", self.posted_at.format("%Y-%m-%d %H:%M UTC"), self.chatter_login, message)
+    }
 }
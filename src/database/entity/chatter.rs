@@ -8,18 +8,23 @@ pub struct Chatter {
     pub name: String,
     pub created_at: DateTime<Utc>,
     pub version: i16,
+    /// IANA name saved with `~settz`
+    pub timezone: Option<String>,
+    /// Language of bot's replies saved with `~setlang`, overrides channel's one
+    pub locale: Option<String>,
 }
 
 impl Chatter {
-    const CURRENT_VERSION: i16 = 1_i16;
+    const CURRENT_VERSION: i16 = 3_i16;
 
-    #[allow(dead_code)]
     pub fn new(login: String, name: String) -> Self {
         Self {
             login,
             name,
             created_at: DateTime::<Utc>::from(SystemTime::now()),
-            version: Chatter::CURRENT_VERSION
+            version: Chatter::CURRENT_VERSION,
+            timezone: Option::None,
+            locale: Option::None,
         }
     }
 
@@ -33,38 +38,47 @@ impl Chatter {
             );\
         ").execute(pool).await?;
 
+        sqlx::query("ALTER TABLE chatters ADD COLUMN IF NOT EXISTS timezone varchar(64);")
+            .execute(pool)
+            .await?;
+
+        sqlx::query("ALTER TABLE chatters ADD COLUMN IF NOT EXISTS locale varchar(16);")
+            .execute(pool)
+            .await?;
+
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub async fn find_one(pool: &PgPool, login: String) -> anyhow::Result<Chatter> {
+    pub async fn find_one(pool: &PgPool, login: &str) -> anyhow::Result<Option<Chatter>> {
         let result = sqlx::query_as::<_, Chatter>("\
             SELECT * from chatters \
             WHERE login = $1\
         ")
             .bind(login)
-            .fetch_one(pool)
+            .fetch_optional(pool)
             .await?;
 
         Ok(result)
     }
 
-    #[allow(dead_code)]
+    /// Inserts the chatter or updates the existing one, `created_at` of existing ones is kept
     pub async fn upsert(pool: &PgPool, chatter: Self) -> anyhow::Result<()> {
         sqlx::query("\
-            INSERT INTO chatters (login, name, created_at, version) \
-            VALUES ($1, $2, $3, $4) \
-            ON CONFLICT DO \
+            INSERT INTO chatters (login, name, created_at, version, timezone, locale) \
+            VALUES ($1, $2, $3, $4, $5, $6) \
+            ON CONFLICT (login) DO \
             UPDATE SET \
                 name = $2, \
-                created_at = $3, \
-                version = $4 \
-            WHERE login = $1\
+                version = $4, \
+                timezone = $5, \
+                locale = $6\
         ")
             .bind(chatter.login)
             .bind(chatter.name)
             .bind(chatter.created_at)
             .bind(chatter.version)
+            .bind(chatter.timezone)
+            .bind(chatter.locale)
             .execute(pool)
             .await?;
 
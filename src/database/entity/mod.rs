@@ -1,2 +1,3 @@
 pub mod chat_log_message;
 pub mod chatter;
+pub mod token_blob;
@@ -0,0 +1,69 @@
+use chrono::prelude::*;
+use sqlx::{FromRow, PgPool};
+use std::time::SystemTime;
+
+/// Serialized (and possibly encrypted) set of OAuth tokens
+#[derive(Clone, Debug, FromRow)]
+pub struct TokenBlob {
+    pub name: String,
+    pub payload: Vec<u8>,
+    pub updated_at: DateTime<Utc>,
+    pub version: i16,
+}
+
+impl TokenBlob {
+    const CURRENT_VERSION: i16 = 1_i16;
+
+    pub fn new(name: String, payload: Vec<u8>) -> Self {
+        Self {
+            name,
+            payload,
+            updated_at: DateTime::<Utc>::from(SystemTime::now()),
+            version: TokenBlob::CURRENT_VERSION,
+        }
+    }
+
+    pub async fn db_initialize(pool: &PgPool) -> anyhow::Result<()> {
+        sqlx::query("\
+            CREATE TABLE IF NOT EXISTS token_blobs (\
+                name varchar(255) PRIMARY KEY,\
+                payload bytea NOT NULL,\
+                updated_at timestamptz,\
+                version smallint\
+            );\
+        ").execute(pool).await?;
+
+        Ok(())
+    }
+
+    pub async fn find_one(pool: &PgPool, name: &str) -> anyhow::Result<Option<TokenBlob>> {
+        let result = sqlx::query_as::<_, TokenBlob>("\
+            SELECT * FROM token_blobs \
+            WHERE name = $1\
+        ")
+            .bind(name)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    pub async fn upsert(pool: &PgPool, token_blob: Self) -> anyhow::Result<()> {
+        sqlx::query("\
+            INSERT INTO token_blobs (name, payload, updated_at, version) \
+            VALUES ($1, $2, $3, $4) \
+            ON CONFLICT (name) DO UPDATE SET \
+                payload = EXCLUDED.payload, \
+                updated_at = EXCLUDED.updated_at, \
+                version = EXCLUDED.version\
+        ")
+            .bind(token_blob.name)
+            .bind(token_blob.payload)
+            .bind(token_blob.updated_at)
+            .bind(token_blob.version)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -1,33 +1,92 @@
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions, PgSslMode};
 use tokio::sync::RwLock;
 
-use crate::config::Config;
+use crate::config::{Config, DatabaseBackend, DatabaseConfig};
 use crate::database::entity::chatter::Chatter;
 use crate::database::entity::chat_log_message::ChatLogMessage;
+use crate::database::entity::token_blob::TokenBlob;
+use crate::database::repository::Repositories;
 
 pub mod entity;
+pub mod repository;
+pub mod retention;
+
+const DEFAULT_MAX_CONNECTIONS: u32 = 5;
+
+pub async fn connect_repositories(config: Arc<RwLock<Config>>) -> anyhow::Result<Repositories> {
+    let backend = config.read().await.app_config.database.backend;
+
+    match backend {
+        DatabaseBackend::Memory => {
+            log::warn!("Using in-memory storage, nothing will be persisted between restarts");
+
+            Ok(Repositories::memory())
+        },
+        DatabaseBackend::Postgres => {
+            let pool = connect_db(config).await?;
+
+            Ok(Repositories::postgres(pool))
+        },
+    }
+}
 
 pub async fn connect_db(config: Arc<RwLock<Config>>) -> anyhow::Result<PgPool> {
     let config = config.read().await;
+    let database_config = &config.app_config.database;
+
+    let connect_options = build_connect_options(database_config)?;
 
-    // Do we even need socket option? :thinking_face:
-    let connect_options =  if config.app_config.database.socket.is_some() {
-        let socket = config.app_config.database.socket.as_ref().unwrap();
-        let username = config.app_config.database.username.as_ref().ok_or(anyhow::anyhow!("Expected 'username' property in database configuration block"))?;
-        let password = config.app_config.database.password.as_ref().ok_or(anyhow::anyhow!("Expected 'password' property in database configuration block"))?;
+    let statement_timeout_ms = database_config.statement_timeout_ms;
+    let mut pool_options = PgPoolOptions::new()
+        .max_connections(database_config.max_connections.unwrap_or(DEFAULT_MAX_CONNECTIONS))
+        .after_connect(move |connection| Box::pin(async move {
+            if let Some(statement_timeout_ms) = statement_timeout_ms {
+                sqlx::query(format!("SET statement_timeout = {}", statement_timeout_ms).as_str())
+                    .execute(connection)
+                    .await?;
+            }
+
+            Ok(())
+        }));
+
+    if let Some(connect_timeout_sec) = database_config.connect_timeout_sec {
+        pool_options = pool_options.connect_timeout(Duration::from_secs(connect_timeout_sec));
+    }
+
+    let pool = pool_options.connect_with(connect_options).await?;
+
+    initialize_schema(&pool).await?;
+
+    initialize_tables(&pool).await?;
+
+    Ok(pool)
+}
+
+/// `DATABASE_URL` env variable takes precedence over `url` property, which takes precedence over separate properties
+fn build_connect_options(database_config: &DatabaseConfig) -> anyhow::Result<PgConnectOptions> {
+    let url = std::env::var("DATABASE_URL").ok().or_else(|| database_config.url.clone());
+
+    let connect_options = if let Some(url) = url {
+        PgConnectOptions::from_str(url.as_str())?
+    } else if let Some(socket) = database_config.socket.as_ref() {
+        // Do we even need socket option? :thinking_face:
+        let username = database_config.username.as_ref().ok_or_else(|| anyhow::anyhow!("Expected 'username' property in database configuration block"))?;
+        let password = database_config.password.as_ref().ok_or_else(|| anyhow::anyhow!("Expected 'password' property in database configuration block"))?;
 
         PgConnectOptions::new()
             .socket(socket.as_str())
             .username(username.as_str())
             .password(password.as_str())
     } else {
-        let host = config.app_config.database.host.as_ref().ok_or(anyhow::anyhow!("Expected 'host' property in database configuration block"))?;
-        let port = config.app_config.database.port.ok_or(anyhow::anyhow!("Expected 'port' property in database configuration block"))?;
-        let username = config.app_config.database.username.as_ref().ok_or(anyhow::anyhow!("Expected 'username' property in database configuration block"))?;
-        let password = config.app_config.database.password.as_ref().ok_or(anyhow::anyhow!("Expected 'password' property in database configuration block"))?;
-        let database = config.app_config.database.database.as_ref().ok_or(anyhow::anyhow!("Expected 'database' property in database configuration block"))?;
+        let host = database_config.host.as_ref().ok_or_else(|| anyhow::anyhow!("Expected 'host' property in database configuration block"))?;
+        let port = database_config.port.ok_or_else(|| anyhow::anyhow!("Expected 'port' property in database configuration block"))?;
+        let username = database_config.username.as_ref().ok_or_else(|| anyhow::anyhow!("Expected 'username' property in database configuration block"))?;
+        let password = database_config.password.as_ref().ok_or_else(|| anyhow::anyhow!("Expected 'password' property in database configuration block"))?;
+        let database = database_config.database.as_ref().ok_or_else(|| anyhow::anyhow!("Expected 'database' property in database configuration block"))?;
 
         PgConnectOptions::new()
             .host(host.as_str())
@@ -37,17 +96,17 @@ pub async fn connect_db(config: Arc<RwLock<Config>>) -> anyhow::Result<PgPool> {
             .database(database.as_str())
     };
 
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect_with(connect_options).await?;
+    let connect_options = match database_config.ssl_mode.as_ref() {
+        Some(ssl_mode) => {
+            let ssl_mode = PgSslMode::from_str(ssl_mode.as_str())
+                .map_err(|_| anyhow::anyhow!("Unknown 'ssl_mode' value '{}' in database configuration block", ssl_mode))?;
 
-    sqlx::query("SET application_name = 'develbot';").execute(&pool).await?;
-
-    initialize_schema(&pool).await?;
-
-    initialize_tables(&pool).await?;
+            connect_options.ssl_mode(ssl_mode)
+        },
+        None => connect_options,
+    };
 
-    Ok(pool)
+    Ok(connect_options.application_name("develbot"))
 }
 
 pub async fn initialize_schema(pg_pool: &PgPool) -> anyhow::Result<()> {
@@ -61,6 +120,7 @@ pub async fn initialize_schema(pg_pool: &PgPool) -> anyhow::Result<()> {
 pub async fn initialize_tables(pg_pool: &sqlx::Pool<sqlx::Postgres>) -> anyhow::Result<()> {
     Chatter::db_initialize(pg_pool).await?;
     ChatLogMessage::db_initialize(pg_pool).await?;
+    TokenBlob::db_initialize(pg_pool).await?;
 
     Ok(())
 }
@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::prelude::*;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+
+use crate::database::entity::chat_log_message::ChatLogMessage;
+use crate::database::entity::chatter::Chatter;
+use crate::database::repository::{ChatLogRepository, ChatterRepository, TokenRepository};
+
+/// Storage that lives as long as the process does, handy for tests and small deployments
+#[derive(Default)]
+pub struct MemoryChatterRepository {
+    chatters: Mutex<HashMap<String, Chatter>>,
+}
+
+#[async_trait]
+impl ChatterRepository for MemoryChatterRepository {
+    async fn find_one(&self, login: &str) -> anyhow::Result<Option<Chatter>> {
+        let chatters = self.chatters.lock().unwrap();
+
+        Ok(chatters.get(login).cloned())
+    }
+
+    async fn upsert(&self, mut chatter: Chatter) -> anyhow::Result<()> {
+        let mut chatters = self.chatters.lock().unwrap();
+
+        if let Some(existing) = chatters.get(chatter.login.as_str()) {
+            chatter.created_at = existing.created_at;
+        }
+
+        chatters.insert(chatter.login.clone(), chatter);
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryTokenRepository {
+    payload: Mutex<Option<Vec<u8>>>,
+}
+
+#[async_trait]
+impl TokenRepository for MemoryTokenRepository {
+    async fn load_tokens(&self) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self.payload.lock().unwrap().clone())
+    }
+
+    async fn save_tokens(&self, payload: Vec<u8>) -> anyhow::Result<()> {
+        *self.payload.lock().unwrap() = Option::Some(payload);
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct MemoryChatLogs {
+    archive: Vec<ChatLogMessage>,
+    last_id: i32,
+    messages: Vec<ChatLogMessage>,
+}
+
+impl MemoryChatLogs {
+    /// Channel's messages, newest first
+    fn channel_messages<'a>(&'a self, channel_login: &'a str) -> impl Iterator<Item = &'a ChatLogMessage> + 'a {
+        self.messages.iter()
+            .rev()
+            .filter(move |message| message.channel_login == channel_login)
+    }
+
+    fn remove_ids(&mut self, ids: &[Option<i32>], archive: bool) -> u64 {
+        let (removed, kept): (Vec<ChatLogMessage>, Vec<ChatLogMessage>) = self.messages.drain(..)
+            .partition(|message| ids.contains(&message.id));
+
+        self.messages = kept;
+
+        let removed_count = removed.len() as u64;
+
+        if archive {
+            self.archive.extend(removed);
+        }
+
+        removed_count
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryChatLogRepository {
+    logs: Mutex<MemoryChatLogs>,
+}
+
+fn contains_words(message: &str, text: &str) -> bool {
+    let message_words: Vec<String> = message
+        .split(|c: char| !c.is_alphanumeric())
+        .map(|word| word.to_lowercase())
+        .collect();
+
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .all(|word| message_words.contains(&word.to_lowercase()))
+}
+
+fn page(messages: Vec<ChatLogMessage>, limit: i64, offset: i64) -> Vec<ChatLogMessage> {
+    messages.into_iter()
+        .skip(offset.max(0) as usize)
+        .take(limit.max(0) as usize)
+        .collect()
+}
+
+#[async_trait]
+impl ChatLogRepository for MemoryChatLogRepository {
+    async fn insert(&self, chat_log_message: ChatLogMessage) -> anyhow::Result<()> {
+        let mut logs = self.logs.lock().unwrap();
+
+        logs.last_id += 1;
+
+        let mut chat_log_message = chat_log_message;
+        chat_log_message.id = Option::Some(logs.last_id);
+
+        // Keep messages sorted by posting time, they mostly arrive in order anyway
+        let position = logs.messages.iter()
+            .rposition(|message| message.posted_at <= chat_log_message.posted_at)
+            .map_or(0, |position| position + 1);
+
+        logs.messages.insert(position, chat_log_message);
+
+        Ok(())
+    }
+
+    async fn find_by_chatter(&self, channel_login: &str, chatter_login: &str, limit: i64, offset: i64) -> anyhow::Result<Vec<ChatLogMessage>> {
+        let logs = self.logs.lock().unwrap();
+        let messages = logs.channel_messages(channel_login)
+            .filter(|message| message.chatter_login == chatter_login)
+            .cloned()
+            .collect();
+
+        Ok(page(messages, limit, offset))
+    }
+
+    async fn find_first_by_chatter(&self, channel_login: &str, chatter_login: &str) -> anyhow::Result<Option<ChatLogMessage>> {
+        let logs = self.logs.lock().unwrap();
+        let message = logs.channel_messages(channel_login).filter(|message| message.chatter_login == chatter_login).last().cloned();
+
+        Ok(message)
+    }
+
+    async fn find_last_by_chatter(&self, channel_login: &str, chatter_login: &str) -> anyhow::Result<Option<ChatLogMessage>> {
+        let logs = self.logs.lock().unwrap();
+        let message = logs.channel_messages(channel_login).find(|message| message.chatter_login == chatter_login).cloned();
+
+        Ok(message)
+    }
+
+    async fn search(&self, channel_login: &str, text: &str, limit: i64, offset: i64) -> anyhow::Result<Vec<ChatLogMessage>> {
+        let logs = self.logs.lock().unwrap();
+        let messages = logs.channel_messages(channel_login)
+            .filter(|message| contains_words(message.message.as_str(), text))
+            .cloned()
+            .collect();
+
+        Ok(page(messages, limit, offset))
+    }
+
+    fn stream_by_channel<'a>(&'a self, channel_login: &'a str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> BoxStream<'a, anyhow::Result<ChatLogMessage>> {
+        let logs = self.logs.lock().unwrap();
+        let mut messages: Vec<anyhow::Result<ChatLogMessage>> = logs.channel_messages(channel_login)
+            .filter(|message| from.map_or(true, |from| message.posted_at >= from))
+            .filter(|message| to.map_or(true, |to| message.posted_at < to))
+            .cloned()
+            .map(Ok)
+            .collect();
+
+        messages.reverse();
+
+        futures::stream::iter(messages).boxed()
+    }
+
+    async fn remove_older_than(&self, channel_login: &str, before: DateTime<Utc>, chunk_size: i64, archive: bool) -> anyhow::Result<u64> {
+        let mut logs = self.logs.lock().unwrap();
+        let ids: Vec<Option<i32>> = logs.channel_messages(channel_login)
+            .filter(|message| message.posted_at < before)
+            .take(chunk_size.max(0) as usize)
+            .map(|message| message.id)
+            .collect();
+
+        Ok(logs.remove_ids(&ids, archive))
+    }
+
+    async fn remove_over_limit(&self, channel_login: &str, max_rows: i64, chunk_size: i64, archive: bool) -> anyhow::Result<u64> {
+        let mut logs = self.logs.lock().unwrap();
+        let ids: Vec<Option<i32>> = logs.channel_messages(channel_login)
+            .skip(max_rows.max(0) as usize)
+            .take(chunk_size.max(0) as usize)
+            .map(|message| message.id)
+            .collect();
+
+        Ok(logs.remove_ids(&ids, archive))
+    }
+
+    async fn pseudonymize_older_than(&self, channel_login: &str, before: DateTime<Utc>, chunk_size: i64, key: &[u8]) -> anyhow::Result<u64> {
+        let mut logs = self.logs.lock().unwrap();
+        let logs = &mut *logs;
+        let messages = logs.messages.iter_mut()
+            .chain(logs.archive.iter_mut())
+            .filter(|message| message.channel_login == channel_login)
+            .filter(|message| message.posted_at < before && !message.chatter_login.starts_with("anon-"))
+            .take(chunk_size.max(0) as usize);
+
+        let mut updated = 0_u64;
+
+        for message in messages {
+            message.chatter_login = ChatLogMessage::pseudonym(key, message.chatter_login.as_str())?;
+            updated += 1;
+        }
+
+        Ok(updated)
+    }
+
+    async fn delete_by_chatter(&self, channel_login: &str, chatter_login: &str) -> anyhow::Result<u64> {
+        let mut logs = self.logs.lock().unwrap();
+        let is_kept = |message: &ChatLogMessage| message.channel_login != channel_login || message.chatter_login != chatter_login;
+
+        let count_before = logs.messages.len() + logs.archive.len();
+
+        logs.messages.retain(is_kept);
+        logs.archive.retain(is_kept);
+
+        Ok((count_before - logs.messages.len() - logs.archive.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::*;
+    use futures::TryStreamExt;
+
+    use crate::database::entity::chat_log_message::ChatLogMessage;
+    use crate::database::repository::ChatLogRepository;
+    use crate::database::repository::postgres::PgChatLogRepository;
+
+    use super::MemoryChatLogRepository;
+
+    const KEY: &[u8] = b"pseudonym-key";
+
+    async fn repository_with_messages() -> MemoryChatLogRepository {
+        let repository = MemoryChatLogRepository::default();
+        let messages = vec![
+            ("pepega", "forsen", "hello chat", 1),
+            ("pepega", "nymn", "hello forsen", 2),
+            ("pepega", "forsen", "bye chat", 3),
+            ("other", "forsen", "hello other chat", 4),
+        ];
+
+        for (channel, chatter, text, day) in messages {
+            let posted_at = Utc.ymd(2021, 5, day).and_hms(12, 0, 0);
+            let message = ChatLogMessage::new(channel.to_string(), chatter.to_string(), text.to_string(), posted_at);
+
+            repository.insert(message).await.unwrap();
+        }
+
+        repository
+    }
+
+    #[tokio::test]
+    async fn history_is_scoped_by_channel() {
+        let repository = repository_with_messages().await;
+
+        let messages = repository.find_by_chatter("pepega", "forsen", 10, 0).await.unwrap();
+        let texts: Vec<&str> = messages.iter().map(|message| message.message.as_str()).collect();
+        assert_eq!(texts, vec!["bye chat", "hello chat"]);
+
+        let first = repository.find_first_by_chatter("pepega", "forsen").await.unwrap().unwrap();
+        assert_eq!(first.message, "hello chat");
+
+        let last = repository.find_last_by_chatter("other", "forsen").await.unwrap().unwrap();
+        assert_eq!(last.message, "hello other chat");
+    }
+
+    #[tokio::test]
+    async fn search_matches_all_words() {
+        let repository = repository_with_messages().await;
+
+        let messages = repository.search("pepega", "Hello", 10, 0).await.unwrap();
+        assert_eq!(messages.len(), 2);
+
+        let messages = repository.search("pepega", "hello chat", 10, 0).await.unwrap();
+        assert_eq!(messages.len(), 1);
+
+        let messages = repository.search("pepega", "hello", 1, 1).await.unwrap();
+        assert_eq!(messages[0].message, "hello chat");
+    }
+
+    #[tokio::test]
+    async fn retention_works() {
+        let repository = repository_with_messages().await;
+
+        let removed = repository.remove_older_than("pepega", Utc.ymd(2021, 5, 1).and_hms(13, 0, 0), 10, true).await.unwrap();
+        assert_eq!(removed, 1);
+
+        let removed = repository.remove_over_limit("pepega", 1, 10, false).await.unwrap();
+        assert_eq!(removed, 1);
+
+        let exported: Vec<ChatLogMessage> = repository.stream_by_channel("pepega", None, None).try_collect().await.unwrap();
+        assert_eq!(exported.len(), 1);
+        assert_eq!(exported[0].message, "bye chat");
+
+        let updated = repository.pseudonymize_older_than("other", Utc.ymd(2021, 6, 1).and_hms(0, 0, 0), 10, KEY).await.unwrap();
+        assert_eq!(updated, 1);
+        assert!(repository.find_last_by_chatter("other", "forsen").await.unwrap().is_none());
+
+        // One message is left in the logs and one more is in the archive
+        let deleted = repository.delete_by_chatter("pepega", "forsen").await.unwrap();
+        assert_eq!(deleted, 2);
+    }
+
+    #[tokio::test]
+    async fn pseudonymization_covers_archive() {
+        let repository = repository_with_messages().await;
+
+        repository.remove_older_than("pepega", Utc.ymd(2021, 5, 1).and_hms(13, 0, 0), 10, true).await.unwrap();
+
+        let updated = repository.pseudonymize_older_than("pepega", Utc.ymd(2021, 6, 1).and_hms(0, 0, 0), 2, KEY).await.unwrap();
+        assert_eq!(updated, 2);
+
+        // Archived message is in the next chunk
+        let updated = repository.pseudonymize_older_than("pepega", Utc.ymd(2021, 6, 1).and_hms(0, 0, 0), 10, KEY).await.unwrap();
+        assert_eq!(updated, 1);
+        assert_eq!(repository.pseudonymize_older_than("pepega", Utc.ymd(2021, 6, 1).and_hms(0, 0, 0), 10, KEY).await.unwrap(), 0);
+
+        // Nothing is left under forsen's login, archived message included
+        let deleted = repository.delete_by_chatter("pepega", "forsen").await.unwrap();
+        assert_eq!(deleted, 0);
+    }
+
+    #[test]
+    fn pseudonym_is_keyed_hmac() {
+        // HMAC-SHA256 test case 2 of RFC 4231
+        assert_eq!(ChatLogMessage::pseudonym(b"Jefe", "what do ya want for nothing?").unwrap(), "anon-5bdcc146bf60");
+        assert_ne!(ChatLogMessage::pseudonym(KEY, "forsen").unwrap(), ChatLogMessage::pseudonym(b"other-key", "forsen").unwrap());
+    }
+
+    /// Runs only with `DEVELBOT_TEST_DATABASE_URL` pointing at a scratch Postgres database
+    #[tokio::test]
+    async fn pseudonyms_match_postgres() {
+        let url = match std::env::var("DEVELBOT_TEST_DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+
+        let pool = sqlx::PgPool::connect(url.as_str()).await.unwrap();
+        ChatLogMessage::db_initialize(&pool).await.unwrap();
+        sqlx::query("DELETE FROM chat_logs WHERE channel_login = 'pseudonym_test'").execute(&pool).await.unwrap();
+
+        let memory = MemoryChatLogRepository::default();
+        let postgres = PgChatLogRepository::new(pool);
+        let posted_at = Utc.ymd(2021, 5, 1).and_hms(12, 0, 0);
+        let before = Utc.ymd(2021, 6, 1).and_hms(0, 0, 0);
+
+        for repository in [&memory as &dyn ChatLogRepository, &postgres as &dyn ChatLogRepository].iter() {
+            repository.insert(ChatLogMessage::new("pseudonym_test".to_string(), "forsen".to_string(), "hello".to_string(), posted_at)).await.unwrap();
+            assert_eq!(repository.pseudonymize_older_than("pseudonym_test", before, 10, KEY).await.unwrap(), 1);
+        }
+
+        let from_memory: Vec<ChatLogMessage> = memory.stream_by_channel("pseudonym_test", None, None).try_collect().await.unwrap();
+        let from_postgres: Vec<ChatLogMessage> = postgres.stream_by_channel("pseudonym_test", None, None).try_collect().await.unwrap();
+
+        assert_eq!(from_memory[0].chatter_login, ChatLogMessage::pseudonym(KEY, "forsen").unwrap());
+        assert_eq!(from_postgres[0].chatter_login, from_memory[0].chatter_login);
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::prelude::*;
+use futures::stream::BoxStream;
+
+use crate::database::entity::chat_log_message::ChatLogMessage;
+use crate::database::entity::chatter::Chatter;
+
+pub mod memory;
+pub mod postgres;
+
+#[async_trait]
+pub trait ChatterRepository: Send + Sync {
+    async fn find_one(&self, login: &str) -> anyhow::Result<Option<Chatter>>;
+
+    async fn upsert(&self, chatter: Chatter) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+pub trait ChatLogRepository: Send + Sync {
+    async fn insert(&self, chat_log_message: ChatLogMessage) -> anyhow::Result<()>;
+
+    /// Latest messages of a chatter in a channel, newest first
+    async fn find_by_chatter(&self, channel_login: &str, chatter_login: &str, limit: i64, offset: i64) -> anyhow::Result<Vec<ChatLogMessage>>;
+
+    async fn find_first_by_chatter(&self, channel_login: &str, chatter_login: &str) -> anyhow::Result<Option<ChatLogMessage>>;
+
+    async fn find_last_by_chatter(&self, channel_login: &str, chatter_login: &str) -> anyhow::Result<Option<ChatLogMessage>>;
+
+    /// Search of messages containing all of the given words, newest first
+    async fn search(&self, channel_login: &str, text: &str, limit: i64, offset: i64) -> anyhow::Result<Vec<ChatLogMessage>>;
+
+    /// Streams channel's messages in chronological order, optionally limited to `[from, to)` range
+    fn stream_by_channel<'a>(&'a self, channel_login: &'a str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> BoxStream<'a, anyhow::Result<ChatLogMessage>>;
+
+    /// Removes a chunk of channel's messages posted before `before`, returns amount of removed messages
+    async fn remove_older_than(&self, channel_login: &str, before: DateTime<Utc>, chunk_size: i64, archive: bool) -> anyhow::Result<u64>;
+
+    /// Removes a chunk of channel's messages that don't fit into latest `max_rows`, returns amount of removed messages
+    async fn remove_over_limit(&self, channel_login: &str, max_rows: i64, chunk_size: i64, archive: bool) -> anyhow::Result<u64>;
+
+    /// Replaces chatter logins of a chunk of channel's messages posted before `before` with `ChatLogMessage::pseudonym`s
+    /// keyed with `key`, returns amount of updated messages
+    async fn pseudonymize_older_than(&self, channel_login: &str, before: DateTime<Utc>, chunk_size: i64, key: &[u8]) -> anyhow::Result<u64>;
+
+    /// Deletes every logged message of a chatter in a channel, archived ones included
+    async fn delete_by_chatter(&self, channel_login: &str, chatter_login: &str) -> anyhow::Result<u64>;
+}
+
+/// Keeps OAuth tokens as a single opaque blob, so they can be encrypted as a whole
+#[async_trait]
+pub trait TokenRepository: Send + Sync {
+    async fn load_tokens(&self) -> anyhow::Result<Option<Vec<u8>>>;
+
+    async fn save_tokens(&self, payload: Vec<u8>) -> anyhow::Result<()>;
+}
+
+/// Storage handles shared by every feature of the bot
+#[derive(Clone)]
+pub struct Repositories {
+    pub chat_logs: Arc<dyn ChatLogRepository>,
+    pub chatters: Arc<dyn ChatterRepository>,
+    pub tokens: Arc<dyn TokenRepository>,
+    /// `None` for in-memory storage
+    pool: Option<sqlx::PgPool>,
+}
+
+impl Repositories {
+    pub fn memory() -> Self {
+        Self {
+            chat_logs: Arc::new(memory::MemoryChatLogRepository::default()),
+            chatters: Arc::new(memory::MemoryChatterRepository::default()),
+            tokens: Arc::new(memory::MemoryTokenRepository::default()),
+            pool: Option::None,
+        }
+    }
+
+    pub fn postgres(pool: sqlx::PgPool) -> Self {
+        Self {
+            chat_logs: Arc::new(postgres::PgChatLogRepository::new(pool.clone())),
+            chatters: Arc::new(postgres::PgChatterRepository::new(pool.clone())),
+            tokens: Arc::new(postgres::PgTokenRepository::new(pool.clone())),
+            pool: Option::Some(pool),
+        }
+    }
+
+    /// Checks the database answers, in-memory storage always does
+    pub async fn ping(&self) -> anyhow::Result<()> {
+        if let Some(pool) = self.pool.as_ref() {
+            sqlx::query("SELECT 1").execute(pool).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Waits for running queries and closes database connections, repositories can't be used afterwards
+    pub async fn close(&self) {
+        if let Some(pool) = self.pool.as_ref() {
+            pool.close().await;
+        }
+    }
+}
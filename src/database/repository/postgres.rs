@@ -0,0 +1,117 @@
+use async_trait::async_trait;
+use chrono::prelude::*;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use sqlx::PgPool;
+
+use crate::database::entity::chat_log_message::ChatLogMessage;
+use crate::database::entity::chatter::Chatter;
+use crate::database::entity::token_blob::TokenBlob;
+use crate::database::repository::{ChatLogRepository, ChatterRepository, TokenRepository};
+
+pub struct PgChatterRepository {
+    pool: PgPool,
+}
+
+impl PgChatterRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+        }
+    }
+}
+
+#[async_trait]
+impl ChatterRepository for PgChatterRepository {
+    async fn find_one(&self, login: &str) -> anyhow::Result<Option<Chatter>> {
+        Chatter::find_one(&self.pool, login).await
+    }
+
+    async fn upsert(&self, chatter: Chatter) -> anyhow::Result<()> {
+        Chatter::upsert(&self.pool, chatter).await
+    }
+}
+
+pub struct PgTokenRepository {
+    pool: PgPool,
+}
+
+impl PgTokenRepository {
+    const BLOB_NAME: &'static str = "default";
+
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+        }
+    }
+}
+
+#[async_trait]
+impl TokenRepository for PgTokenRepository {
+    async fn load_tokens(&self) -> anyhow::Result<Option<Vec<u8>>> {
+        let token_blob = TokenBlob::find_one(&self.pool, PgTokenRepository::BLOB_NAME).await?;
+
+        Ok(token_blob.map(|token_blob| token_blob.payload))
+    }
+
+    async fn save_tokens(&self, payload: Vec<u8>) -> anyhow::Result<()> {
+        TokenBlob::upsert(&self.pool, TokenBlob::new(PgTokenRepository::BLOB_NAME.to_string(), payload)).await
+    }
+}
+
+pub struct PgChatLogRepository {
+    pool: PgPool,
+}
+
+impl PgChatLogRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+        }
+    }
+}
+
+#[async_trait]
+impl ChatLogRepository for PgChatLogRepository {
+    async fn insert(&self, chat_log_message: ChatLogMessage) -> anyhow::Result<()> {
+        ChatLogMessage::insert(&self.pool, chat_log_message).await
+    }
+
+    async fn find_by_chatter(&self, channel_login: &str, chatter_login: &str, limit: i64, offset: i64) -> anyhow::Result<Vec<ChatLogMessage>> {
+        ChatLogMessage::find_by_chatter(&self.pool, channel_login, chatter_login, limit, offset).await
+    }
+
+    async fn find_first_by_chatter(&self, channel_login: &str, chatter_login: &str) -> anyhow::Result<Option<ChatLogMessage>> {
+        ChatLogMessage::find_first_by_chatter(&self.pool, channel_login, chatter_login).await
+    }
+
+    async fn find_last_by_chatter(&self, channel_login: &str, chatter_login: &str) -> anyhow::Result<Option<ChatLogMessage>> {
+        ChatLogMessage::find_last_by_chatter(&self.pool, channel_login, chatter_login).await
+    }
+
+    async fn search(&self, channel_login: &str, text: &str, limit: i64, offset: i64) -> anyhow::Result<Vec<ChatLogMessage>> {
+        ChatLogMessage::search(&self.pool, channel_login, text, limit, offset).await
+    }
+
+    fn stream_by_channel<'a>(&'a self, channel_login: &'a str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> BoxStream<'a, anyhow::Result<ChatLogMessage>> {
+        ChatLogMessage::stream_by_channel(&self.pool, channel_login, from, to)
+            .map_err(anyhow::Error::from)
+            .boxed()
+    }
+
+    async fn remove_older_than(&self, channel_login: &str, before: DateTime<Utc>, chunk_size: i64, archive: bool) -> anyhow::Result<u64> {
+        ChatLogMessage::remove_older_than(&self.pool, channel_login, before, chunk_size, archive).await
+    }
+
+    async fn remove_over_limit(&self, channel_login: &str, max_rows: i64, chunk_size: i64, archive: bool) -> anyhow::Result<u64> {
+        ChatLogMessage::remove_over_limit(&self.pool, channel_login, max_rows, chunk_size, archive).await
+    }
+
+    async fn pseudonymize_older_than(&self, channel_login: &str, before: DateTime<Utc>, chunk_size: i64, key: &[u8]) -> anyhow::Result<u64> {
+        ChatLogMessage::pseudonymize_older_than(&self.pool, channel_login, before, chunk_size, key).await
+    }
+
+    async fn delete_by_chatter(&self, channel_login: &str, chatter_login: &str) -> anyhow::Result<u64> {
+        ChatLogMessage::delete_by_chatter(&self.pool, channel_login, chatter_login).await
+    }
+}
@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use chrono::prelude::*;
+use tokio_util::sync::CancellationToken;
+
+use crate::bot::BotContext;
+use crate::config::{ChannelInfo, RetentionConfig};
+use crate::database::repository::ChatLogRepository;
+use crate::shutdown::PendingTasks;
+
+const DEFAULT_CHECK_EVERY_SEC: u64 = 3600;
+const DEFAULT_CHUNK_SIZE: i64 = 1000;
+
+/// Spawns background task that periodically applies channels' retention policies until the bot shuts down
+pub async fn start_retention_job(context: BotContext) {
+    let (period, chunk_size) = {
+        let config = context.config.read().await;
+        let global = &config.app_config.global;
+
+        (
+            Duration::from_secs(global.retention_check_every_sec.unwrap_or(DEFAULT_CHECK_EVERY_SEC)),
+            global.retention_chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE).max(1),
+        )
+    };
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+
+        loop {
+            tokio::select! {
+                _ = context.shutdown.cancelled() => break,
+                _ = interval.tick() => {},
+            }
+
+            let (channels, pseudonym_key): (Vec<ChannelInfo>, Option<String>) = {
+                let config = context.config.read().await;
+
+                (config.app_config.channels.clone(), config.app_config.global.pseudonym_key.clone())
+            };
+
+            let chunks = Chunks {
+                size: chunk_size,
+                pending: &context.pending,
+                shutdown: &context.shutdown,
+            };
+
+            for channel_info in channels {
+                let retention = match channel_info.retention.as_ref() {
+                    Some(retention) => retention,
+                    None => continue,
+                };
+
+                let result = apply_retention(context.repositories.chat_logs.as_ref(), channel_info.channel.as_str(), retention, &chunks, pseudonym_key.as_deref()).await;
+
+                if let Err(error) = result {
+                    log::error!("Failed to apply retention policy for channel '{}': {}", channel_info.channel, error);
+                }
+            }
+        }
+    });
+}
+
+/// How retention work is split, every chunk is pending work of the shutdown and none starts after it began
+pub struct Chunks<'a> {
+    pub size: i64,
+    pub pending: &'a PendingTasks,
+    pub shutdown: &'a CancellationToken,
+}
+
+/// Removes, archives and pseudonymizes channel's logs chunk by chunk, so the table isn't locked for long
+pub async fn apply_retention(
+    chat_logs: &dyn ChatLogRepository,
+    channel: &str,
+    retention: &RetentionConfig,
+    chunks: &Chunks<'_>,
+    pseudonym_key: Option<&str>,
+) -> anyhow::Result<()> {
+    let channel = channel.to_lowercase();
+    let chunk_size = chunks.size;
+    let now = Utc::now();
+
+    if let Some(max_age_days) = retention.max_age_days {
+        let before = now - chrono::Duration::days(max_age_days.into());
+        let removed = repeat_in_chunks(chunks, || chat_logs.remove_older_than(channel.as_str(), before, chunk_size, retention.archive)).await?;
+
+        if removed > 0 {
+            log::info!("Removed {} messages older than {} days from logs of channel '{}'", removed, max_age_days, channel);
+        }
+    }
+
+    if let Some(max_rows) = retention.max_rows {
+        let removed = repeat_in_chunks(chunks, || chat_logs.remove_over_limit(channel.as_str(), max_rows, chunk_size, retention.archive)).await?;
+
+        if removed > 0 {
+            log::info!("Removed {} messages over the limit of {} from logs of channel '{}'", removed, max_rows, channel);
+        }
+    }
+
+    if let Some(pseudonymize_after_days) = retention.pseudonymize_after_days {
+        let key = pseudonym_key.filter(|key| !key.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Can't pseudonymize logs without global.pseudonym_key"))?;
+        let before = now - chrono::Duration::days(pseudonymize_after_days.into());
+        let updated = repeat_in_chunks(chunks, || chat_logs.pseudonymize_older_than(channel.as_str(), before, chunk_size, key.as_bytes())).await?;
+
+        if updated > 0 {
+            log::info!("Pseudonymized {} messages older than {} days in logs of channel '{}'", updated, pseudonymize_after_days, channel);
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs chunked operation until it affects less rows than a full chunk or the bot shuts down, returns total of affected rows
+async fn repeat_in_chunks<F, Fut>(chunks: &Chunks<'_>, mut operation: F) -> anyhow::Result<u64>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<u64>>,
+{
+    let mut total = 0_u64;
+
+    loop {
+        // Tracked before checking, so the shutdown either waits for the chunk or it doesn't start
+        let _guard = chunks.pending.track();
+
+        if chunks.shutdown.is_cancelled() {
+            return Ok(total);
+        }
+
+        let affected = operation().await?;
+        total += affected;
+
+        if affected < chunks.size as u64 {
+            return Ok(total);
+        }
+    }
+}
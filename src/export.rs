@@ -0,0 +1,171 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::str::FromStr;
+
+use chrono::prelude::*;
+use clap::ArgMatches;
+use futures::TryStreamExt;
+use serde::Serialize;
+
+use crate::database::entity::chat_log_message::ChatLogMessage;
+use crate::database::repository::{ChatLogRepository, Repositories};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+    Text,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" => Ok(ExportFormat::Jsonl),
+            "text" => Ok(ExportFormat::Text),
+            _ => Err(anyhow::anyhow!("Unknown export format '{}', expected one of: csv, jsonl, text", value)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ExportRecord<'a> {
+    id: Option<i32>,
+    channel: &'a str,
+    chatter: &'a str,
+    message: &'a str,
+    posted_at: String,
+}
+
+impl<'a> From<&'a ChatLogMessage> for ExportRecord<'a> {
+    fn from(message: &'a ChatLogMessage) -> Self {
+        Self {
+            id: message.id,
+            channel: message.channel_login.as_str(),
+            chatter: message.chatter_login.as_str(),
+            message: message.message.as_str(),
+            posted_at: message.posted_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Accepts either full RFC 3339 datetime or a plain `YYYY-MM-DD` date (midnight UTC)
+pub fn parse_datetime_arg(value: &str) -> anyhow::Result<DateTime<Utc>> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("Couldn't parse '{}', expected RFC 3339 datetime or YYYY-MM-DD date", value))?;
+
+    Ok(DateTime::<Utc>::from_utc(date.and_hms(0, 0, 0), Utc))
+}
+
+fn format_text_line(message: &ChatLogMessage) -> String {
+    format!("[{}] <{}> {}", message.posted_at.format("%H:%M:%S"), message.chatter_login, message.message)
+}
+
+fn format_day_changed_line(date: Date<Utc>) -> String {
+    format!("--- Day changed {}", date.format("%a %b %d %Y"))
+}
+
+/// Writes channel's logs into `writer` row by row, returns amount of exported messages
+pub async fn export_chat_logs<W: Write>(
+    chat_logs: &dyn ChatLogRepository,
+    channel_login: &str,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    format: ExportFormat,
+    writer: W,
+) -> anyhow::Result<u64> {
+    let mut rows = chat_logs.stream_by_channel(channel_login, from, to);
+    let mut count = 0_u64;
+
+    match format {
+        ExportFormat::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(writer);
+
+            while let Some(message) = rows.try_next().await? {
+                csv_writer.serialize(ExportRecord::from(&message))?;
+                count += 1;
+            }
+
+            csv_writer.flush()?;
+        },
+        ExportFormat::Jsonl => {
+            let mut writer = writer;
+
+            while let Some(message) = rows.try_next().await? {
+                serde_json::to_writer(&mut writer, &ExportRecord::from(&message))?;
+                writer.write_all(b"\n")?;
+                count += 1;
+            }
+
+            writer.flush()?;
+        },
+        ExportFormat::Text => {
+            let mut writer = writer;
+            let mut current_date: Option<Date<Utc>> = Option::None;
+
+            while let Some(message) = rows.try_next().await? {
+                let date = message.posted_at.date();
+
+                if current_date != Option::Some(date) {
+                    writeln!(writer, "{}", format_day_changed_line(date))?;
+                    current_date = Option::Some(date);
+                }
+
+                writeln!(writer, "{}", format_text_line(&message))?;
+                count += 1;
+            }
+
+            writer.flush()?;
+        },
+    }
+
+    Ok(count)
+}
+
+/// Entry point of `export` subcommand
+pub async fn run_export(repositories: &Repositories, args: &ArgMatches<'static>) -> anyhow::Result<()> {
+    let channel_login = args.value_of("channel").unwrap().to_lowercase(); // Safe unwrap, arg is required
+    let format = ExportFormat::from_str(args.value_of("format").unwrap())?; // Safe unwrap, arg has default value
+    let output = args.value_of("output").unwrap(); // Safe unwrap, arg is required
+
+    let from = args.value_of("from").map(parse_datetime_arg).transpose()?;
+    let to = args.value_of("to").map(parse_datetime_arg).transpose()?;
+
+    let file = File::create(output)?;
+    let count = export_chat_logs(repositories.chat_logs.as_ref(), channel_login.as_str(), from, to, format, BufWriter::new(file)).await?;
+
+    log::info!("Exported {} messages of channel '{}' to '{}'", count, channel_login, output);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::*;
+
+    use crate::database::entity::chat_log_message::ChatLogMessage;
+
+    use super::{format_day_changed_line, format_text_line, parse_datetime_arg};
+
+    #[test]
+    fn parse_datetime_arg_works() {
+        assert_eq!(parse_datetime_arg("2021-05-01").unwrap(), Utc.ymd(2021, 5, 1).and_hms(0, 0, 0));
+        assert_eq!(parse_datetime_arg("2021-05-01T12:30:00+02:00").unwrap(), Utc.ymd(2021, 5, 1).and_hms(10, 30, 0));
+        assert!(parse_datetime_arg("yesterday").is_err());
+    }
+
+    #[test]
+    fn text_format_works() {
+        let posted_at = Utc.ymd(2021, 5, 1).and_hms(9, 5, 3);
+        let message = ChatLogMessage::new("pepega".to_string(), "forsen".to_string(), "hello".to_string(), posted_at);
+
+        assert_eq!(format_text_line(&message), "[09:05:03] <forsen> hello");
+        assert_eq!(format_day_changed_line(posted_at.date()), "--- Day changed Sat May 01 2021");
+    }
+}
@@ -0,0 +1,238 @@
+//! `/healthz` and `/readyz` for Docker and process managers, `/readyz` answers with a JSON report of every component
+
+use std::convert::Infallible;
+use std::time::Duration;
+
+use hyper::{Body, Request, Response, Server, StatusCode};
+use hyper::server::conn::AddrIncoming;
+use hyper::service::{make_service_fn, service_fn};
+use serde::Serialize;
+use tokio::sync::watch;
+
+use crate::auth::checker::TokenHealth;
+use crate::bot::BotContext;
+use crate::supervisor::BotState;
+
+pub const DEFAULT_HEALTH_HOST: &str = "127.0.0.1";
+pub const DEFAULT_HEALTH_PORT: u64 = 8098;
+/// Unanswered database ping counts as failed after that long
+const DATABASE_PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ComponentHealth {
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ChannelHealth {
+    pub channel: String,
+    /// One of `starting`, `running` or `restarting`
+    pub bot: &'static str,
+    /// Whether the server confirmed the bot joined the channel
+    pub joined: bool,
+    pub restarts: u32,
+    pub last_message_at: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct HealthReport {
+    pub shutting_down: bool,
+    pub database: ComponentHealth,
+    pub tokens: ComponentHealth,
+    pub channels: Vec<ChannelHealth>,
+}
+
+impl HealthReport {
+    /// The process works and isn't on its way out, restarting it won't help otherwise
+    pub fn is_alive(&self) -> bool {
+        !self.shutting_down
+    }
+
+    /// Every channel's bot is in chat and can reach the database and Twitch
+    pub fn is_ready(&self) -> bool {
+        self.is_alive()
+            && self.database.ok
+            && self.tokens.ok
+            && self.channels.iter().all(|channel| channel.bot == "running" && channel.joined)
+    }
+}
+
+fn token_health(health: &TokenHealth) -> ComponentHealth {
+    match health {
+        TokenHealth::Healthy { .. } => ComponentHealth { ok: true, error: Option::None },
+        TokenHealth::Starting => ComponentHealth { ok: false, error: Option::Some("first token check hasn't completed yet".to_string()) },
+        TokenHealth::Failing { attempts, last_error, .. } => ComponentHealth {
+            ok: false,
+            error: Option::Some(format!("token checks failed {} times in a row: {}", attempts, last_error)),
+        },
+        TokenHealth::Stopped => ComponentHealth { ok: false, error: Option::Some("token checks are stopped".to_string()) },
+    }
+}
+
+async fn collect_report(context: &BotContext, token_health_receiver: &watch::Receiver<TokenHealth>) -> HealthReport {
+    let database = match tokio::time::timeout(DATABASE_PING_TIMEOUT, context.repositories.ping()).await {
+        Ok(Ok(())) => ComponentHealth { ok: true, error: Option::None },
+        Ok(Err(error)) => ComponentHealth { ok: false, error: Option::Some(error.to_string()) },
+        Err(_) => ComponentHealth { ok: false, error: Option::Some("database didn't answer in time".to_string()) },
+    };
+
+    let tokens = token_health(&token_health_receiver.borrow());
+    let joined = context.chat_connections.read().await.joined_channels().await;
+
+    let channels = context.supervisor.statuses().into_iter()
+        .map(|(channel, status)| {
+            let (bot, error) = match status.state {
+                BotState::Starting => ("starting", Option::None),
+                BotState::Running => ("running", Option::None),
+                BotState::Restarting { error, .. } => ("restarting", Option::Some(error)),
+            };
+
+            ChannelHealth {
+                joined: joined.get(channel.as_str()).copied().unwrap_or(false),
+                channel,
+                bot,
+                restarts: status.restarts,
+                last_message_at: status.last_message_at.map(|time| time.to_rfc3339()),
+                error,
+            }
+        })
+        .collect();
+
+    HealthReport {
+        shutting_down: context.shutdown.is_cancelled(),
+        database,
+        tokens,
+        channels,
+    }
+}
+
+fn json_response(ok: bool, body: String) -> Response<Body> {
+    let status = if ok { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body))
+        .unwrap() // Safe unwrap, status and header are valid
+}
+
+/// `/healthz` only looks at the shutdown flag, so it answers even while the database or chat connections hang
+fn liveness_response(shutting_down: bool) -> Response<Body> {
+    json_response(!shutting_down, serde_json::json!({ "shutting_down": shutting_down }).to_string())
+}
+
+fn readiness_response(report: &HealthReport) -> Response<Body> {
+    json_response(report.is_ready(), serde_json::to_string(report).unwrap_or_default())
+}
+
+fn not_found() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::empty())
+        .unwrap() // Safe unwrap, status is valid
+}
+
+/// Spawns the endpoints' server, it stops once the bot shuts down
+pub async fn start_health_server(context: BotContext) -> anyhow::Result<()> {
+    let (host, port) = {
+        let config = context.config.read().await;
+        let global = &config.app_config.global;
+
+        (global.health_host.clone().unwrap_or_else(|| DEFAULT_HEALTH_HOST.to_string()), global.health_port.unwrap_or(DEFAULT_HEALTH_PORT))
+    };
+
+    if port == 0 {
+        log::info!("Health endpoints are disabled");
+        return Ok(());
+    }
+
+    let host_port = format!("{}:{}", host, port);
+    let address = tokio::net::lookup_host(host_port.as_str()).await?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Couldn't resolve health endpoints' address '{}'", host_port))?;
+
+    // Token client is locked for the whole token check, its health isn't
+    let token_health_receiver = context.token_client.read().await.get_health();
+    let shutdown = context.shutdown.clone();
+
+    let make_service = make_service_fn(move |_| {
+        let context = context.clone();
+        let token_health_receiver = token_health_receiver.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let context = context.clone();
+                let token_health_receiver = token_health_receiver.clone();
+
+                async move {
+                    let response = match request.uri().path() {
+                        "/healthz" => liveness_response(context.shutdown.is_cancelled()),
+                        "/readyz" => readiness_response(&collect_report(&context, &token_health_receiver).await),
+                        _ => not_found(),
+                    };
+
+                    Ok::<_, Infallible>(response)
+                }
+            }))
+        }
+    });
+
+    let incoming = AddrIncoming::bind(&address)
+        .map_err(|error| anyhow::anyhow!("Failed to setup health endpoints on {}: {}", address, error))?;
+
+    log::info!("Serving /healthz and /readyz on {}", incoming.local_addr());
+
+    let server = Server::builder(incoming)
+        .serve(make_service)
+        .with_graceful_shutdown(async move {
+            shutdown.cancelled().await;
+        });
+
+    tokio::spawn(async move {
+        if let Err(error) = server.await {
+            log::error!("Health endpoints failed: {}", error);
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::StatusCode;
+
+    use super::{ChannelHealth, ComponentHealth, HealthReport, liveness_response, readiness_response};
+
+    fn report(joined: bool, database_ok: bool) -> HealthReport {
+        HealthReport {
+            shutting_down: false,
+            database: ComponentHealth { ok: database_ok, error: Option::None },
+            tokens: ComponentHealth { ok: true, error: Option::None },
+            channels: vec![ChannelHealth {
+                channel: "pepega".to_string(),
+                bot: "running",
+                joined,
+                restarts: 0,
+                last_message_at: Option::None,
+                error: Option::None,
+            }],
+        }
+    }
+
+    #[test]
+    fn responses_work() {
+        assert_eq!(liveness_response(false).status(), StatusCode::OK);
+        assert_eq!(liveness_response(true).status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        assert_eq!(readiness_response(&report(true, true)).status(), StatusCode::OK);
+        assert_eq!(readiness_response(&report(false, true)).status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(readiness_response(&report(true, false)).status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let mut shutting_down = report(true, true);
+        shutting_down.shutting_down = true;
+
+        assert_eq!(readiness_response(&shutting_down).status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
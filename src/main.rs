@@ -4,7 +4,6 @@ extern crate enum_dispatch;
 extern crate hyper;
 extern crate log;
 extern crate log4rs;
-extern crate oneshot;
 extern crate serde;
 extern crate sqlx;
 extern crate tokio;
@@ -15,20 +14,36 @@ extern crate twitch_oauth2;
 
 use std::sync::Arc;
 
-use clap::{App, Arg, ArgMatches, crate_authors, crate_description, crate_name, crate_version};
+use clap::{App, Arg, ArgMatches, crate_authors, crate_description, crate_name, crate_version, SubCommand};
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
 
-use bot::Bot;
+use bot::BotContext;
 
 use crate::auth::TokenClient;
+use crate::auth::token_store::open_token_store;
 use crate::config::Config;
-use crate::database::connect_db;
+use crate::config::reload::start_config_reload;
+use crate::database::connect_repositories;
+use crate::database::retention::start_retention_job;
+use crate::health::start_health_server;
+use crate::messages::cooldowns::CooldownTracker;
+use crate::messages::i18n::Localizer;
+use crate::shutdown::{PendingTasks, shut_down, wait_for_signal};
+use crate::supervisor::BotSupervisor;
+use crate::twitch::chat::ChatConnections;
+use crate::twitch::irc::init_irc_url;
 
 mod auth;
 mod bot;
 mod messages;
 mod config;
 mod database;
+mod export;
+mod health;
+mod shutdown;
+mod supervisor;
+mod twitch;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -55,6 +70,111 @@ async fn main() -> anyhow::Result<()> {
                 .default_value("./configs/logger.toml")
                 .help("Specifies custom path to bot's logger config file")
                 .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("login-flow")
+                .long("login-flow")
+                .value_name("FLOW")
+                .possible_values(&["redirect", "device"])
+                .help("Overrides how users login, 'device' prints a code to enter at twitch.tv/activate instead of redirecting")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("set")
+                .long("set")
+                .value_name("KEY=VALUE")
+                .help("Overrides config value, e.g. database.port=5433, takes precedence over the file and DEVELBOT_* env variables")
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Exports chat logs of a channel and exits")
+                .arg(
+                    Arg::with_name("channel")
+                        .long("channel")
+                        .value_name("CHANNEL")
+                        .help("Channel which logs should be exported")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .value_name("DATETIME")
+                        .help("Export messages posted at or after given RFC 3339 datetime or YYYY-MM-DD date")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .value_name("DATETIME")
+                        .help("Export messages posted before given RFC 3339 datetime or YYYY-MM-DD date")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .possible_values(&["csv", "jsonl", "text"])
+                        .default_value("jsonl")
+                        .help("Output format")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("File to write exported logs to")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("config")
+                .about("Works with the config file and exits")
+                .subcommand(
+                    SubCommand::with_name("check")
+                        .about("Reports every problem of the config without starting the bot"),
+                )
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Prints effective config and where every value comes from"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("auth")
+                .about("Manages stored OAuth tokens and exits")
+                .subcommand(
+                    SubCommand::with_name("login")
+                        .about("Logs in and stores the token")
+                        .arg(
+                            Arg::with_name("account")
+                                .long("account")
+                                .value_name("ACCOUNT")
+                                .default_value("bot")
+                                .help("One of 'bot', 'bot:<identity>' or 'broadcaster:<channel>'")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("status")
+                        .about("Shows login, scopes and expiry of every stored token"),
+                )
+                .subcommand(
+                    SubCommand::with_name("revoke")
+                        .about("Revokes stored tokens and removes them from the store")
+                        .arg(
+                            Arg::with_name("account")
+                                .long("account")
+                                .value_name("ACCOUNT")
+                                .help("One of 'bot', 'bot:<identity>' or 'broadcaster:<channel>', every stored token if omitted")
+                                .takes_value(true),
+                        ),
+                ),
         );
 
     // Parse args
@@ -73,51 +193,92 @@ async fn main() -> anyhow::Result<()> {
 
     log::debug!("{} version {} starting...", crate_name!(), crate_version!());
 
+    // Config might be broken, so it's checked before loading
+    let config_command = args_arc.read().await.subcommand_matches("config")
+        .map(|config_args| config_args.subcommand_name().map(|name| name.to_string()));
+
+    match config_command {
+        Some(Some(command)) if command == "check" => return Config::check(args_arc.clone()).await,
+        Some(Some(command)) if command == "show" => return Config::show(args_arc.clone()).await,
+        Some(_) => return Err(anyhow::anyhow!("Expected one of subcommands: check, show")),
+        None => {},
+    }
+
     // Create & load the config
     let config = Config::from_args(args_arc.clone()).await?;
     let config_arc = Arc::new(RwLock::new(config));
 
+    // Run one-off subcommands instead of the bot
+    let export_args = args_arc.read().await.subcommand_matches("export").cloned();
+
+    if let Some(export_args) = export_args {
+        let repositories = connect_repositories(config_arc.clone()).await?;
+
+        return export::run_export(&repositories, &export_args).await;
+    }
+
+    let auth_args = args_arc.read().await.subcommand_matches("auth").cloned();
+
+    if let Some(auth_args) = auth_args {
+        let repositories = connect_repositories(config_arc.clone()).await?;
+        let token_store = open_token_store(&*config_arc.read().await, &repositories).await?;
+        let token_client = TokenClient::new(config_arc.clone(), token_store).await?;
+
+        return auth::cli::run_auth(Arc::new(RwLock::new(token_client)), &auth_args).await;
+    }
+
+    // Connect to the storage
+    let repositories = connect_repositories(config_arc.clone()).await?;
+
     // Create token checker client
-    let token_client = TokenClient::new(config_arc.clone()).await?;
+    let token_store = open_token_store(&*config_arc.read().await, &repositories).await?;
+    let token_client = TokenClient::new(config_arc.clone(), token_store).await?;
     let token_client_ref: Arc<RwLock<TokenClient>> = Arc::new(RwLock::new(token_client));
 
-    // Create database pool and establish connection
-    let db_pool = connect_db(config_arc.clone()).await?;
-    let db_pool = Arc::new(RwLock::new(db_pool));
+    // Acquire tokens and keep checking them in background, shared by all channels' bots
+    TokenClient::start(token_client_ref.clone()).await?;
 
     let channels = async {
         config_arc.read().await.app_config.channels.clone()
     }.await;
 
+    init_irc_url(config_arc.read().await.app_config.twitch.irc_url());
+
+    // One chat connection per identity, shared by its channels
+    let chat_connections = Arc::new(RwLock::new(ChatConnections::new(token_client_ref.clone())));
+
+    let bot_context = BotContext {
+        args: args_arc.clone(),
+        chat_connections,
+        config: config_arc.clone(),
+        cooldowns: CooldownTracker::default(),
+        localizer: Arc::new(Localizer::new()?),
+        pending: PendingTasks::default(),
+        repositories: repositories.clone(),
+        shutdown: CancellationToken::new(),
+        supervisor: BotSupervisor::default(),
+        token_client: token_client_ref.clone(),
+    };
+
     for channel_info in channels {
-        let args_arc = args_arc.clone();
-        let config_arc = config_arc.clone();
-        let db_pool = db_pool.clone();
-        let token_client_ref = token_client_ref.clone();
-
-        tokio::spawn(async move {
-            let bot = Bot::<'static>::new(
-                args_arc,
-                channel_info.clone(),
-                config_arc,
-                db_pool,
-                token_client_ref
-            ).await;
-
-            if bot.is_err() {
-                log::error!("Failed to create bot for channel {}", channel_info.channel.as_str());
-                return;
-            }
-
-            let result = bot.unwrap().start_chat_processor().await;
-
-            if result.is_err() {
-                log::error!("Failed to start/keep alive chat processor for channel {}", channel_info.channel.as_str());
-            }
-        });
+        bot_context.spawn_bot(channel_info);
     }
 
-    loop {
-        std::thread::sleep(std::time::Duration::from_secs(5));
-    }
+    // Let Docker and process managers know whether the bot is connected
+    start_health_server(bot_context.clone()).await?;
+
+    // Keep chat logs within channels' retention policies
+    start_retention_job(bot_context.clone()).await;
+
+    // Apply config changes on SIGHUP or when the file changes
+    start_config_reload(bot_context.clone()).await?;
+
+    let signal = wait_for_signal().await?;
+    log::info!("Got {}, shutting down", signal);
+
+    shut_down(&bot_context).await?;
+
+    log::info!("Shut down cleanly");
+
+    Ok(())
 }
@@ -0,0 +1,68 @@
+use fluent_bundle::FluentValue;
+use twitch_irc::message::PrivmsgMessage;
+
+use crate::config::settings::ChannelSettings;
+use crate::messages::commands::CommandItem;
+use crate::messages::core::{Command, CommandInfo, get_command_args};
+use crate::messages::processor::MessageProcessor;
+
+pub struct ForgetMeCommand {
+    command_info: CommandInfo,
+}
+
+impl ForgetMeCommand {
+    pub fn default() -> CommandItem {
+        let command_info = CommandInfo::new(
+            "Forget Me",
+            "Deletes all of your logged messages in this channel: ~forgetme confirm",
+            "forgetme"
+        );
+
+        let command = Self {
+            command_info
+        };
+
+        CommandItem::ForgetMeCommand(command)
+    }
+}
+
+impl Command for ForgetMeCommand {
+    fn get_command_info(&self) -> &CommandInfo {
+        &self.command_info
+    }
+
+    fn execute(&self, message_processor: &MessageProcessor, message: &PrivmsgMessage, settings: &ChannelSettings) {
+        let channel = message.channel_login.clone();
+        let chatter_login = message.sender.login.clone();
+        let chatter_name = message.sender.name.clone();
+
+        if get_command_args(message).first() != Some(&"confirm") {
+            let reply = message_processor.tr(settings.locale.as_str(), "forget-me-confirm", &[
+                ("name", FluentValue::from(chatter_name.as_str())),
+                ("prefix", FluentValue::from(settings.prefix.as_str())),
+            ]);
+
+            message_processor.send_privmsg(channel, reply);
+            return;
+        }
+
+        let locale = settings.locale.clone();
+        let pending = message_processor.get_pending();
+        let message_processor = message_processor.clone();
+
+        pending.spawn(async move {
+            let result = message_processor.get_repositories().chat_logs
+                .delete_by_chatter(channel.as_str(), chatter_login.as_str())
+                .await;
+
+            match result {
+                Ok(deleted) => {
+                    log::info!("Deleted {} logged messages of '{}' in channel '{}' on their request", deleted, chatter_login, channel);
+                    let reply = message_processor.tr(locale.as_str(), "forget-me-deleted", &[("name", FluentValue::from(chatter_name.as_str())), ("count", FluentValue::from(deleted))]);
+                    message_processor.send_privmsg(channel, reply);
+                },
+                Err(error) => log::error!("Failed to delete logs of '{}': {}", chatter_login, error),
+            }
+        });
+    }
+}
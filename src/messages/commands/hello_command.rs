@@ -1,5 +1,7 @@
+use fluent_bundle::FluentValue;
 use twitch_irc::message::PrivmsgMessage;
 
+use crate::config::settings::ChannelSettings;
 use crate::messages::commands::CommandItem;
 use crate::messages::core::{Command, CommandInfo};
 use crate::messages::processor::MessageProcessor;
@@ -29,8 +31,10 @@ impl Command for HelloCommand {
         &self.command_info
     }
 
-    fn execute(&self, message_processor: &MessageProcessor, message: &PrivmsgMessage) {
+    fn execute(&self, message_processor: &MessageProcessor, message: &PrivmsgMessage, settings: &ChannelSettings) {
         let channel = message.channel_login.clone();
-        message_processor.send_privmsg(channel, format!("Hello, {}!", message.sender.name));
+        let reply = message_processor.tr(settings.locale.as_str(), "hello", &[("name", FluentValue::from(message.sender.name.as_str()))]);
+
+        message_processor.send_privmsg(channel, reply);
     }
 }
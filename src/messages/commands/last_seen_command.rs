@@ -0,0 +1,71 @@
+use fluent_bundle::FluentValue;
+use twitch_irc::message::PrivmsgMessage;
+
+use crate::config::settings::ChannelSettings;
+use crate::messages::commands::CommandItem;
+use crate::messages::core::{Command, CommandInfo, get_command_args, is_moderator, LOG_ENTRY_MAX_LEN, normalize_login};
+use crate::messages::processor::MessageProcessor;
+
+pub struct LastSeenCommand {
+    command_info: CommandInfo,
+}
+
+impl LastSeenCommand {
+    pub fn default() -> CommandItem {
+        let command_info = CommandInfo::new(
+            "Last Seen",
+            "Shows when a chatter last wrote in this channel: ~lastseen <user>",
+            "lastseen"
+        );
+
+        let command = Self {
+            command_info
+        };
+
+        CommandItem::LastSeenCommand(command)
+    }
+}
+
+impl Command for LastSeenCommand {
+    fn get_command_info(&self) -> &CommandInfo {
+        &self.command_info
+    }
+
+    fn execute(&self, message_processor: &MessageProcessor, message: &PrivmsgMessage, settings: &ChannelSettings) {
+        if !is_moderator(message) {
+            return;
+        }
+
+        let channel = message.channel_login.clone();
+
+        let chatter_login = match get_command_args(message).first() {
+            Some(login) => normalize_login(login),
+            None => {
+                message_processor.send_privmsg(channel, message_processor.tr(settings.locale.as_str(), "last-seen-usage", &[("prefix", FluentValue::from(settings.prefix.as_str()))]));
+                return;
+            },
+        };
+
+        let locale = settings.locale.clone();
+        let pending = message_processor.get_pending();
+        let message_processor = message_processor.clone();
+
+        pending.spawn(async move {
+            let result = message_processor.get_repositories().chat_logs
+                .find_last_by_chatter(channel.as_str(), chatter_login.as_str())
+                .await;
+
+            match result {
+                Ok(Some(entry)) => {
+                    let reply = message_processor.tr(locale.as_str(), "last-seen", &[("line", FluentValue::from(entry.to_chat_line(LOG_ENTRY_MAX_LEN)))]);
+                    message_processor.send_privmsg(channel, reply);
+                },
+                Ok(None) => {
+                    let reply = message_processor.tr(locale.as_str(), "never-seen", &[("login", FluentValue::from(chatter_login.as_str()))]);
+                    message_processor.send_privmsg(channel, reply);
+                },
+                Err(error) => log::error!("Failed to fetch last message of '{}': {}", chatter_login, error),
+            }
+        });
+    }
+}
@@ -0,0 +1,74 @@
+use fluent_bundle::FluentValue;
+use twitch_irc::message::PrivmsgMessage;
+
+use crate::config::settings::ChannelSettings;
+use crate::messages::commands::CommandItem;
+use crate::messages::core::{Command, CommandInfo, get_command_args, is_moderator, LOG_ENTRY_MAX_LEN, LOG_PAGE_SIZE, normalize_login, parse_page};
+use crate::messages::processor::MessageProcessor;
+
+pub struct LogsCommand {
+    command_info: CommandInfo,
+}
+
+impl LogsCommand {
+    pub fn default() -> CommandItem {
+        let command_info = CommandInfo::new(
+            "Chatter Logs",
+            "Shows latest messages of a chatter in this channel: ~logs <user> [page]",
+            "logs"
+        );
+
+        let command = Self {
+            command_info
+        };
+
+        CommandItem::LogsCommand(command)
+    }
+}
+
+impl Command for LogsCommand {
+    fn get_command_info(&self) -> &CommandInfo {
+        &self.command_info
+    }
+
+    fn execute(&self, message_processor: &MessageProcessor, message: &PrivmsgMessage, settings: &ChannelSettings) {
+        if !is_moderator(message) {
+            return;
+        }
+
+        let channel = message.channel_login.clone();
+        let args = get_command_args(message);
+
+        let chatter_login = match args.first() {
+            Some(login) => normalize_login(login),
+            None => {
+                message_processor.send_privmsg(channel, message_processor.tr(settings.locale.as_str(), "logs-usage", &[("prefix", FluentValue::from(settings.prefix.as_str()))]));
+                return;
+            },
+        };
+
+        let page = args.get(1).and_then(|arg| parse_page(arg)).unwrap_or(1);
+        let locale = settings.locale.clone();
+        let pending = message_processor.get_pending();
+        let message_processor = message_processor.clone();
+
+        pending.spawn(async move {
+            let result = message_processor.get_repositories().chat_logs
+                .find_by_chatter(channel.as_str(), chatter_login.as_str(), LOG_PAGE_SIZE, (page - 1) * LOG_PAGE_SIZE)
+                .await;
+
+            match result {
+                Ok(entries) if entries.is_empty() => {
+                    let reply = message_processor.tr(locale.as_str(), "logs-empty", &[("login", FluentValue::from(chatter_login.as_str())), ("page", FluentValue::from(page))]);
+                    message_processor.send_privmsg(channel, reply);
+                },
+                Ok(entries) => {
+                    let lines: Vec<String> = entries.iter().map(|entry| entry.to_chat_line(LOG_ENTRY_MAX_LEN)).collect();
+                    let reply = message_processor.tr(locale.as_str(), "log-page", &[("page", FluentValue::from(page)), ("lines", FluentValue::from(lines.join(" | ")))]);
+                    message_processor.send_privmsg(channel, reply);
+                },
+                Err(error) => log::error!("Failed to fetch logs of '{}': {}", chatter_login, error),
+            }
+        });
+    }
+}
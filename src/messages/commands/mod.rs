@@ -1,17 +1,43 @@
 use enum_dispatch::enum_dispatch;
 use twitch_irc::message::PrivmsgMessage;
 
-use current_command::CurrentCommand;
+use first_message_command::FirstMessageCommand;
+use forget_me_command::ForgetMeCommand;
 use hello_command::HelloCommand;
+use last_seen_command::LastSeenCommand;
+use logs_command::LogsCommand;
+use search_command::SearchCommand;
+use set_language_command::SetLanguageCommand;
+use set_timezone_command::SetTimezoneCommand;
+use status_command::StatusCommand;
+use time_command::TimeCommand;
 
+use crate::config::settings::ChannelSettings;
 use crate::messages::core::{Command, CommandInfo};
 use crate::messages::MessageProcessor;
 
-pub mod current_command;
+pub mod first_message_command;
+pub mod forget_me_command;
 pub mod hello_command;
+pub mod last_seen_command;
+pub mod logs_command;
+pub mod search_command;
+pub mod set_language_command;
+pub mod set_timezone_command;
+pub mod status_command;
+pub mod time_command;
 
 #[enum_dispatch]
+#[allow(clippy::enum_variant_names)]
 pub enum CommandItem {
-    CurrentCommand(CurrentCommand),
+    FirstMessageCommand(FirstMessageCommand),
+    ForgetMeCommand(ForgetMeCommand),
     HelloCommand(HelloCommand),
+    LastSeenCommand(LastSeenCommand),
+    LogsCommand(LogsCommand),
+    SearchCommand(SearchCommand),
+    SetLanguageCommand(SetLanguageCommand),
+    SetTimezoneCommand(SetTimezoneCommand),
+    StatusCommand(StatusCommand),
+    TimeCommand(TimeCommand),
 }
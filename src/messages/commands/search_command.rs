@@ -0,0 +1,95 @@
+use fluent_bundle::FluentValue;
+use twitch_irc::message::PrivmsgMessage;
+
+use crate::config::settings::ChannelSettings;
+use crate::messages::commands::CommandItem;
+use crate::messages::core::{Command, CommandInfo, get_command_args, is_moderator, LOG_ENTRY_MAX_LEN, LOG_PAGE_SIZE, parse_page};
+use crate::messages::processor::MessageProcessor;
+
+pub struct SearchCommand {
+    command_info: CommandInfo,
+}
+
+impl SearchCommand {
+    pub fn default() -> CommandItem {
+        let command_info = CommandInfo::new(
+            "Search Logs",
+            "Searches this channel's logs for given words: ~search <text> [#page]",
+            "search"
+        );
+
+        let command = Self {
+            command_info
+        };
+
+        CommandItem::SearchCommand(command)
+    }
+}
+
+/// Splits search query from the trailing `#N` page argument
+fn split_query_and_page(args: &[&str]) -> (String, i64) {
+    match args.split_last() {
+        Some((last, rest)) if last.starts_with('#') && !rest.is_empty() => match parse_page(last) {
+            Some(page) => (rest.join(" "), page),
+            None => (args.join(" "), 1),
+        },
+        _ => (args.join(" "), 1),
+    }
+}
+
+impl Command for SearchCommand {
+    fn get_command_info(&self) -> &CommandInfo {
+        &self.command_info
+    }
+
+    fn execute(&self, message_processor: &MessageProcessor, message: &PrivmsgMessage, settings: &ChannelSettings) {
+        if !is_moderator(message) {
+            return;
+        }
+
+        let channel = message.channel_login.clone();
+        let (query, page) = split_query_and_page(&get_command_args(message));
+
+        if query.is_empty() {
+            message_processor.send_privmsg(channel, message_processor.tr(settings.locale.as_str(), "search-usage", &[("prefix", FluentValue::from(settings.prefix.as_str()))]));
+            return;
+        }
+
+        let locale = settings.locale.clone();
+        let pending = message_processor.get_pending();
+        let message_processor = message_processor.clone();
+
+        pending.spawn(async move {
+            let result = message_processor.get_repositories().chat_logs
+                .search(channel.as_str(), query.as_str(), LOG_PAGE_SIZE, (page - 1) * LOG_PAGE_SIZE)
+                .await;
+
+            match result {
+                Ok(entries) if entries.is_empty() => {
+                    let reply = message_processor.tr(locale.as_str(), "search-empty", &[("query", FluentValue::from(query.as_str())), ("page", FluentValue::from(page))]);
+                    message_processor.send_privmsg(channel, reply);
+                },
+                Ok(entries) => {
+                    let lines: Vec<String> = entries.iter().map(|entry| entry.to_chat_line(LOG_ENTRY_MAX_LEN)).collect();
+                    let reply = message_processor.tr(locale.as_str(), "log-page", &[("page", FluentValue::from(page)), ("lines", FluentValue::from(lines.join(" | ")))]);
+                    message_processor.send_privmsg(channel, reply);
+                },
+                Err(error) => log::error!("Failed to search logs for '{}': {}", query, error),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_query_and_page;
+
+    #[test]
+    fn split_query_and_page_works() {
+        assert_eq!(split_query_and_page(&["hello", "world"]), ("hello world".to_string(), 1));
+        assert_eq!(split_query_and_page(&["hello", "#2"]), ("hello".to_string(), 2));
+        assert_eq!(split_query_and_page(&["#2"]), ("#2".to_string(), 1));
+        assert_eq!(split_query_and_page(&["hello", "2"]), ("hello 2".to_string(), 1));
+        assert_eq!(split_query_and_page(&[]), ("".to_string(), 1));
+    }
+}
@@ -0,0 +1,99 @@
+use fluent_bundle::FluentValue;
+use twitch_irc::message::PrivmsgMessage;
+
+use crate::config::settings::ChannelSettings;
+use crate::database::entity::chatter::Chatter;
+use crate::messages::commands::CommandItem;
+use crate::messages::core::{Command, CommandInfo, get_command_args};
+use crate::messages::i18n::{find_locale, locale_names};
+use crate::messages::processor::MessageProcessor;
+
+pub struct SetLanguageCommand {
+    command_info: CommandInfo,
+}
+
+impl SetLanguageCommand {
+    pub fn default() -> CommandItem {
+        let command_info = CommandInfo::new(
+            "Set Language",
+            "Saves the language bot answers you in, in every channel: ~setlang <locale|reset>",
+            "setlang"
+        );
+
+        let command = Self {
+            command_info
+        };
+
+        CommandItem::SetLanguageCommand(command)
+    }
+}
+
+impl Command for SetLanguageCommand {
+    fn get_command_info(&self) -> &CommandInfo {
+        &self.command_info
+    }
+
+    fn execute(&self, message_processor: &MessageProcessor, message: &PrivmsgMessage, settings: &ChannelSettings) {
+        let channel = message.channel_login.clone();
+        let chatter_login = message.sender.login.clone();
+        let chatter_name = message.sender.name.clone();
+        let query = get_command_args(message).join(" ");
+
+        let locale = match query.as_str() {
+            "" => {
+                let reply = message_processor.tr(settings.locale.as_str(), "set-language-usage", &[
+                    ("prefix", FluentValue::from(settings.prefix.as_str())),
+                    ("locales", FluentValue::from(locale_names())),
+                ]);
+
+                message_processor.send_privmsg(channel, reply);
+                return;
+            },
+            "reset" => Option::None,
+            _ => match find_locale(query.as_str()) {
+                Some(locale) => Option::Some(locale),
+                None => {
+                    let reply = message_processor.tr(settings.locale.as_str(), "set-language-unknown", &[
+                        ("name", FluentValue::from(chatter_name.as_str())),
+                        ("query", FluentValue::from(query.as_str())),
+                        ("locales", FluentValue::from(locale_names())),
+                    ]);
+
+                    message_processor.send_privmsg(channel, reply);
+                    return;
+                },
+            },
+        };
+
+        let pending = message_processor.get_pending();
+        let message_processor = message_processor.clone();
+
+        pending.spawn(async move {
+            let chatters = message_processor.get_repositories().chatters.clone();
+
+            let result = async {
+                let mut chatter = chatters.find_one(chatter_login.as_str()).await?
+                    .unwrap_or_else(|| Chatter::new(chatter_login.clone(), chatter_name.clone()));
+
+                chatter.name = chatter_name.clone();
+                chatter.locale = locale.map(|locale| locale.to_string());
+
+                chatters.upsert(chatter).await
+            }.await;
+
+            // Answered in the language the chatter will be answered in from now on
+            match (result, locale) {
+                (Ok(()), Some(locale)) => {
+                    let reply = message_processor.tr(locale, "set-language-set", &[("name", FluentValue::from(chatter_name.as_str()))]);
+                    message_processor.send_privmsg(channel, reply);
+                },
+                (Ok(()), None) => {
+                    let channel_locale = message_processor.get_settings(channel.as_str()).await.locale;
+                    let reply = message_processor.tr(channel_locale.as_str(), "set-language-removed", &[("name", FluentValue::from(chatter_name.as_str()))]);
+                    message_processor.send_privmsg(channel, reply);
+                },
+                (Err(error), _) => log::error!("Failed to save language of '{}': {}", chatter_login, error),
+            }
+        });
+    }
+}
@@ -0,0 +1,92 @@
+use fluent_bundle::FluentValue;
+use twitch_irc::message::PrivmsgMessage;
+
+use crate::config::settings::ChannelSettings;
+use crate::database::entity::chatter::Chatter;
+use crate::messages::commands::CommandItem;
+use crate::messages::commands::time_command::find_timezone;
+use crate::messages::core::{Command, CommandInfo, get_command_args};
+use crate::messages::processor::MessageProcessor;
+
+pub struct SetTimezoneCommand {
+    command_info: CommandInfo,
+}
+
+impl SetTimezoneCommand {
+    pub fn default() -> CommandItem {
+        let command_info = CommandInfo::new(
+            "Set Timezone",
+            "Saves your timezone, so others can see your local time with ~time <user>: ~settz <zone|city|reset>",
+            "settz"
+        );
+
+        let command = Self {
+            command_info
+        };
+
+        CommandItem::SetTimezoneCommand(command)
+    }
+}
+
+impl Command for SetTimezoneCommand {
+    fn get_command_info(&self) -> &CommandInfo {
+        &self.command_info
+    }
+
+    fn execute(&self, message_processor: &MessageProcessor, message: &PrivmsgMessage, settings: &ChannelSettings) {
+        let channel = message.channel_login.clone();
+        let chatter_login = message.sender.login.clone();
+        let chatter_name = message.sender.name.clone();
+        let query = get_command_args(message).join(" ");
+
+        let timezone = match query.as_str() {
+            "" => {
+                message_processor.send_privmsg(channel, message_processor.tr(settings.locale.as_str(), "set-timezone-usage", &[("prefix", FluentValue::from(settings.prefix.as_str()))]));
+                return;
+            },
+            "reset" => Option::None,
+            _ => match find_timezone(query.as_str()) {
+                Some(timezone) => Option::Some(timezone),
+                None => {
+                    let reply = message_processor.tr(settings.locale.as_str(), "set-timezone-unknown", &[
+                        ("name", FluentValue::from(chatter_name.as_str())),
+                        ("query", FluentValue::from(query.as_str())),
+                    ]);
+
+                    message_processor.send_privmsg(channel, reply);
+                    return;
+                },
+            },
+        };
+
+        let locale = settings.locale.clone();
+        let pending = message_processor.get_pending();
+        let message_processor = message_processor.clone();
+
+        pending.spawn(async move {
+            let chatters = message_processor.get_repositories().chatters.clone();
+
+            let result = async {
+                let mut chatter = chatters.find_one(chatter_login.as_str()).await?
+                    .unwrap_or_else(|| Chatter::new(chatter_login.clone(), chatter_name.clone()));
+
+                chatter.name = chatter_name.clone();
+                chatter.timezone = timezone.map(|timezone| timezone.name().to_string());
+
+                chatters.upsert(chatter).await
+            }.await;
+
+            match (result, timezone) {
+                (Ok(()), Some(timezone)) => {
+                    let reply = message_processor.tr(locale.as_str(), "set-timezone-set", &[("name", FluentValue::from(chatter_name.as_str())), ("timezone", FluentValue::from(timezone.name()))]);
+                    message_processor.send_privmsg(channel, reply);
+                },
+                (Ok(()), None) => {
+                    let reply = message_processor.tr(locale.as_str(), "set-timezone-removed", &[("name", FluentValue::from(chatter_name.as_str()))]);
+                    message_processor.send_privmsg(channel, reply);
+                },
+                (Err(error), _) => log::error!("Failed to save timezone of '{}': {}", chatter_login, error),
+            }
+        });
+    }
+}
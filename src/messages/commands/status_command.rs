@@ -0,0 +1,73 @@
+use fluent_bundle::FluentValue;
+use twitch_irc::message::PrivmsgMessage;
+
+use crate::config::settings::ChannelSettings;
+use crate::messages::commands::CommandItem;
+use crate::messages::core::{Command, CommandInfo, is_moderator};
+use crate::messages::processor::MessageProcessor;
+use crate::supervisor::BotState;
+
+/// Errors are cut to that many characters, so every bot fits into a single chat message
+const ERROR_MAX_LEN: usize = 60;
+
+/// Twitch rejects longer chat messages
+const REPLY_MAX_LEN: usize = 500;
+
+pub struct StatusCommand {
+    command_info: CommandInfo,
+}
+
+impl StatusCommand {
+    pub fn default() -> CommandItem {
+        let command_info = CommandInfo::new(
+            "Status",
+            "Shows state of the channel's bot, every bot is listed by /readyz: ~status",
+            "status"
+        );
+
+        let command = Self {
+            command_info
+        };
+
+        CommandItem::StatusCommand(command)
+    }
+}
+
+impl Command for StatusCommand {
+    fn get_command_info(&self) -> &CommandInfo {
+        &self.command_info
+    }
+
+    fn execute(&self, message_processor: &MessageProcessor, message: &PrivmsgMessage, settings: &ChannelSettings) {
+        if !is_moderator(message) {
+            return;
+        }
+
+        let locale = settings.locale.as_str();
+
+        // Moderators only see their own channel, other channels' errors could reveal their setup
+        let bots: Vec<String> = message_processor.get_supervisor().status_of(message.channel_login.as_str()).into_iter()
+            .map(|status| (message.channel_login.clone(), status))
+            .map(|(channel, status)| match status.state {
+                BotState::Starting => message_processor.tr(locale, "status-starting", &[("channel", FluentValue::from(channel))]),
+                BotState::Running => message_processor.tr(locale, "status-running", &[
+                    ("channel", FluentValue::from(channel)),
+                    ("since", FluentValue::from(status.since.format("%Y-%m-%d %H:%M UTC").to_string())),
+                ]),
+                BotState::Restarting { attempt, backoff_sec, error } => message_processor.tr(locale, "status-restarting", &[
+                    ("channel", FluentValue::from(channel)),
+                    ("attempt", FluentValue::from(attempt)),
+                    ("seconds", FluentValue::from(backoff_sec)),
+                    ("error", FluentValue::from(error.chars().take(ERROR_MAX_LEN).collect::<String>())),
+                ]),
+            })
+            .collect();
+
+        let reply: String = message_processor.tr(locale, "status", &[("bots", FluentValue::from(bots.join(" | ")))])
+            .chars()
+            .take(REPLY_MAX_LEN)
+            .collect();
+
+        message_processor.send_privmsg(message.channel_login.clone(), reply);
+    }
+}
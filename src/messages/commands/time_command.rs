@@ -0,0 +1,189 @@
+use chrono::{DateTime, Datelike};
+use chrono::offset::Utc;
+use chrono_tz::{Tz, TZ_VARIANTS};
+use fluent_bundle::FluentValue;
+use twitch_irc::message::PrivmsgMessage;
+
+use crate::config::settings::ChannelSettings;
+use crate::messages::commands::CommandItem;
+use crate::messages::core::{Command, CommandInfo, get_command_args, normalize_login};
+use crate::messages::processor::MessageProcessor;
+
+fn get_day_suffix(day: u32) -> &'static str {
+    match day {
+        1 | 21 | 31 => "st",
+        2 | 22 => "nd",
+        3 | 23 => "rd",
+        _ => "th"
+    }
+}
+
+/// Timezone by its IANA name or city, e.g. `europe/warsaw`, `Warsaw` or `new york`, case insensitive
+pub fn find_timezone(query: &str) -> Option<Tz> {
+    let query = query.trim().replace(' ', "_");
+
+    TZ_VARIANTS.iter()
+        .find(|timezone| timezone.name().eq_ignore_ascii_case(query.as_str()))
+        .or_else(|| TZ_VARIANTS.iter().find(|timezone| {
+            timezone.name().rsplit('/').next()
+                .map(|city| city.eq_ignore_ascii_case(query.as_str()))
+                .unwrap_or(false)
+        }))
+        .copied()
+}
+
+/// Time as it's usually written in the locale's language
+pub fn format_time(time: &DateTime<Tz>, locale: &str) -> String {
+    let language = locale.split(['-', '_']).next().unwrap_or(locale);
+
+    let format = match language {
+        "en" => format!("%I:%M:%S %p %Z on %-d{} of %B, %Y", get_day_suffix(time.day())),
+        "de" | "pl" | "ru" | "uk" | "cs" | "fi" | "no" | "da" => "%H:%M:%S %Z, %d.%m.%Y".to_string(),
+        "fr" | "es" | "it" | "pt" => "%H:%M:%S %Z, %d/%m/%Y".to_string(),
+        _ => "%Y-%m-%d %H:%M:%S %Z".to_string(),
+    };
+
+    time.format(format.as_str()).to_string()
+}
+
+pub struct TimeCommand {
+    command_info: CommandInfo,
+}
+
+impl TimeCommand {
+    pub fn default() -> CommandItem {
+        let command_info = CommandInfo::new(
+            "Current Time",
+            "Returns current datetime in channel's timezone, given timezone or city, or chatter's saved timezone: ~time [zone|city|user]",
+            "time"
+        ).with_aliases(&["current"]);
+
+        let command = Self {
+            command_info
+        };
+
+        CommandItem::TimeCommand(command)
+    }
+}
+
+impl Command for TimeCommand {
+    fn get_command_info(&self) -> &CommandInfo {
+        &self.command_info
+    }
+
+    fn execute(&self, message_processor: &MessageProcessor, message: &PrivmsgMessage, settings: &ChannelSettings) {
+        let channel = message.channel_login.clone();
+        let args = get_command_args(message);
+        let current_time = Utc::now();
+
+        if args.is_empty() {
+            let time = format_time(&current_time.with_timezone(&settings.timezone), settings.locale.as_str());
+            message_processor.send_privmsg(channel, message_processor.tr(settings.locale.as_str(), "time-current", &[("time", FluentValue::from(time))]));
+            return;
+        }
+
+        if let Some(timezone) = find_timezone(args.join(" ").as_str()) {
+            let time = format_time(&current_time.with_timezone(&timezone), settings.locale.as_str());
+            message_processor.send_privmsg(channel, message_processor.tr(settings.locale.as_str(), "time-current", &[("time", FluentValue::from(time))]));
+            return;
+        }
+
+        let chatter_login = normalize_login(args[0]);
+        let locale = settings.locale.clone();
+        let pending = message_processor.get_pending();
+        let message_processor = message_processor.clone();
+
+        pending.spawn(async move {
+            let result = message_processor.get_repositories().chatters.find_one(chatter_login.as_str()).await;
+
+            let timezone = match result {
+                Ok(chatter) => chatter.and_then(|chatter| chatter.timezone).and_then(|timezone| find_timezone(timezone.as_str())),
+                Err(error) => {
+                    log::error!("Failed to fetch timezone of '{}': {}", chatter_login, error);
+                    return;
+                },
+            };
+
+            match timezone {
+                Some(timezone) => {
+                    let time = format_time(&current_time.with_timezone(&timezone), locale.as_str());
+                    let reply = message_processor.tr(locale.as_str(), "time-of-chatter", &[("login", FluentValue::from(chatter_login.as_str())), ("time", FluentValue::from(time))]);
+                    message_processor.send_privmsg(channel, reply);
+                },
+                None => {
+                    let reply = message_processor.tr(locale.as_str(), "time-unknown", &[("login", FluentValue::from(chatter_login.as_str()))]);
+                    message_processor.send_privmsg(channel, reply);
+                },
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use chrono_tz::Tz;
+
+    use super::{find_timezone, format_time, get_day_suffix};
+
+    #[test]
+    fn get_day_suffix_works() {
+        assert_eq!(get_day_suffix(1), "st");
+        assert_eq!(get_day_suffix(2), "nd");
+        assert_eq!(get_day_suffix(3), "rd");
+        assert_eq!(get_day_suffix(4), "th");
+        assert_eq!(get_day_suffix(5), "th");
+        assert_eq!(get_day_suffix(6), "th");
+        assert_eq!(get_day_suffix(7), "th");
+        assert_eq!(get_day_suffix(8), "th");
+        assert_eq!(get_day_suffix(9), "th");
+        assert_eq!(get_day_suffix(10), "th");
+        assert_eq!(get_day_suffix(11), "th");
+        assert_eq!(get_day_suffix(12), "th");
+        assert_eq!(get_day_suffix(13), "th");
+        assert_eq!(get_day_suffix(14), "th");
+        assert_eq!(get_day_suffix(15), "th");
+        assert_eq!(get_day_suffix(16), "th");
+        assert_eq!(get_day_suffix(17), "th");
+        assert_eq!(get_day_suffix(18), "th");
+        assert_eq!(get_day_suffix(19), "th");
+        assert_eq!(get_day_suffix(20), "th");
+        assert_eq!(get_day_suffix(21), "st");
+        assert_eq!(get_day_suffix(22), "nd");
+        assert_eq!(get_day_suffix(23), "rd");
+        assert_eq!(get_day_suffix(24), "th");
+        assert_eq!(get_day_suffix(25), "th");
+        assert_eq!(get_day_suffix(26), "th");
+        assert_eq!(get_day_suffix(27), "th");
+        assert_eq!(get_day_suffix(28), "th");
+        assert_eq!(get_day_suffix(29), "th");
+        assert_eq!(get_day_suffix(30), "th");
+        assert_eq!(get_day_suffix(31), "st");
+    }
+
+    #[test]
+    fn find_timezone_works() {
+        assert_eq!(find_timezone("Europe/Warsaw"), Option::Some(Tz::Europe__Warsaw));
+        assert_eq!(find_timezone("europe/warsaw"), Option::Some(Tz::Europe__Warsaw));
+        assert_eq!(find_timezone("Warsaw"), Option::Some(Tz::Europe__Warsaw));
+        assert_eq!(find_timezone("new york"), Option::Some(Tz::America__New_York));
+        assert_eq!(find_timezone("UTC"), Option::Some(Tz::UTC));
+        assert_eq!(find_timezone("forsen"), Option::None);
+    }
+
+    #[test]
+    fn format_time_works() {
+        let time = Tz::Europe__Warsaw.ymd(2021, 5, 1).and_hms(18, 30, 5);
+
+        assert_eq!(format_time(&time, "en"), "06:30:05 PM CEST on 1st of May, 2021");
+        assert_eq!(format_time(&time, "pl"), "18:30:05 CEST, 01.05.2021");
+        assert_eq!(format_time(&time, "ja"), "2021-05-01 18:30:05 CEST");
+
+        // Both fall into ISO weeks of the other year
+        let new_years_eve = Tz::UTC.ymd(2024, 12, 31).and_hms(23, 59, 59);
+        let new_year = Tz::UTC.ymd(2021, 1, 1).and_hms(0, 0, 0);
+
+        assert_eq!(format_time(&new_years_eve, "en"), "11:59:59 PM UTC on 31st of December, 2024");
+        assert_eq!(format_time(&new_year, "en"), "12:00:00 AM UTC on 1st of January, 2021");
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::settings::Cooldowns;
+
+/// Remembers when commands were used and channels were greeted last, shared by every channel's bot
+#[derive(Clone, Default)]
+pub struct CooldownTracker {
+    last_used: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+fn is_cooling_down(last_used: &HashMap<String, Instant>, key: &str, cooldown_sec: Option<u64>, now: Instant) -> bool {
+    match (last_used.get(key), cooldown_sec) {
+        (Some(used_at), Some(cooldown_sec)) => now.saturating_duration_since(*used_at) < Duration::from_secs(cooldown_sec),
+        _ => false,
+    }
+}
+
+impl CooldownTracker {
+    /// Whether the chatter can use the command now, the use is recorded if they can
+    pub fn try_use(&self, cooldowns: &Cooldowns, channel: &str, slug: &str, chatter: &str, now: Instant) -> bool {
+        let command_key = format!("{}:command:{}", channel, slug);
+        let chatter_key = format!("{}:chatter:{}", channel, chatter);
+
+        let mut last_used = self.last_used.lock().unwrap();
+
+        if is_cooling_down(&last_used, command_key.as_str(), cooldowns.command_sec, now)
+            || is_cooling_down(&last_used, chatter_key.as_str(), cooldowns.user_sec, now) {
+            return false;
+        }
+
+        last_used.insert(command_key, now);
+        last_used.insert(chatter_key, now);
+
+        true
+    }
+
+    /// Whether the channel can be greeted now, outlives bots so their restarts and reconnects don't greet again
+    pub fn try_greet(&self, channel: &str, greeting_every_sec: u64, now: Instant) -> bool {
+        let greeting_key = format!("{}:greeting", channel);
+        let mut last_used = self.last_used.lock().unwrap();
+
+        if is_cooling_down(&last_used, greeting_key.as_str(), Option::Some(greeting_every_sec), now) {
+            return false;
+        }
+
+        last_used.insert(greeting_key, now);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::config::settings::Cooldowns;
+
+    use super::CooldownTracker;
+
+    #[test]
+    fn cooldowns_work() {
+        let tracker = CooldownTracker::default();
+        let cooldowns = Cooldowns { command_sec: Option::Some(5), user_sec: Option::Some(30) };
+        let now = Instant::now();
+
+        assert!(tracker.try_use(&cooldowns, "pepega", "hello", "forsen", now));
+        // Command is cooling down for everyone
+        assert!(!tracker.try_use(&cooldowns, "pepega", "hello", "nymn", now + Duration::from_secs(1)));
+        // Chatter is cooling down for every command
+        assert!(!tracker.try_use(&cooldowns, "pepega", "current", "forsen", now + Duration::from_secs(10)));
+        assert!(tracker.try_use(&cooldowns, "pepega", "hello", "nymn", now + Duration::from_secs(10)));
+        assert!(tracker.try_use(&cooldowns, "pepega", "current", "forsen", now + Duration::from_secs(31)));
+        // Other channels have their own cooldowns
+        assert!(tracker.try_use(&cooldowns, "forsen", "current", "forsen", now + Duration::from_secs(31)));
+
+        assert!(tracker.try_use(&Cooldowns::default(), "pepega", "current", "forsen", now + Duration::from_secs(31)));
+
+        assert!(tracker.try_greet("pepega", 3600, now));
+        assert!(!tracker.try_greet("pepega", 3600, now + Duration::from_secs(60)));
+        assert!(tracker.try_greet("forsen", 3600, now + Duration::from_secs(60)));
+        assert!(tracker.try_greet("pepega", 3600, now + Duration::from_secs(3600)));
+    }
+}
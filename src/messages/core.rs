@@ -1,10 +1,14 @@
 use enum_dispatch::enum_dispatch;
 use twitch_irc::message::PrivmsgMessage;
 
+use crate::config::settings::ChannelSettings;
+
 use super::processor::MessageProcessor;
 
 #[allow(dead_code)]
 pub struct CommandInfo {
+    /// Other slugs the command is known by
+    aliases: &'static [&'static str],
     /// Description of the command
     description: &'static str,
     /// Readable name of the command
@@ -16,25 +20,41 @@ pub struct CommandInfo {
 impl CommandInfo {
     pub fn new(name: &'static str, description: &'static str, slug: &'static str) -> Self {
         Self {
+            aliases: &[],
             description,
             name,
             slug,
         }
     }
 
+    pub fn with_aliases(mut self, aliases: &'static [&'static str]) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    /// Whether the word after the prefix is command's slug or one of its aliases
+    pub fn matches(&self, slug: &str) -> bool {
+        self.slug == slug || self.aliases.contains(&slug)
+    }
+
     #[allow(dead_code)]
     pub fn get_description(&self) -> &str {
-        self.description.clone()
+        self.description
     }
 
     #[allow(dead_code)]
     pub fn get_name(&self) -> &str {
-        self.name.clone()
+        self.name
     }
 
     #[allow(dead_code)]
     pub fn get_slug(&self) -> &str {
-        self.slug.clone()
+        self.slug
+    }
+
+    /// Slug followed by aliases
+    pub fn get_slugs(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.slug).chain(self.aliases.iter().copied())
     }
 }
 
@@ -42,5 +62,59 @@ impl CommandInfo {
 pub trait Command {
     fn get_command_info(&self) -> &CommandInfo;
 
-    fn execute(&self, message_processor: &MessageProcessor, message: &PrivmsgMessage);
+    fn execute(&self, message_processor: &MessageProcessor, message: &PrivmsgMessage, settings: &ChannelSettings);
+}
+
+/// Whitespace-separated arguments that follow the command slug
+pub fn get_command_args(message: &PrivmsgMessage) -> Vec<&str> {
+    message.message_text.split_whitespace().skip(1).collect()
+}
+
+/// Channel moderators and the broadcaster are allowed to use moderation commands
+pub fn is_moderator(message: &PrivmsgMessage) -> bool {
+    message.badges.iter().any(|badge| badge.name == "moderator" || badge.name == "broadcaster")
+}
+
+/// Chatter login as it's stored in the logs, accepting `@Mentions` too
+pub fn normalize_login(login: &str) -> String {
+    login.trim_start_matches('@').to_lowercase()
+}
+
+/// Amount of log entries shown per page by the log commands
+pub const LOG_PAGE_SIZE: i64 = 3;
+
+/// Longest message text shown per log entry, so a page fits into a single chat message
+pub const LOG_ENTRY_MAX_LEN: usize = 100;
+
+/// Pages past that are shown as the last one, so `(page - 1) * LOG_PAGE_SIZE` can't overflow
+pub const MAX_LOG_PAGE: i64 = 100_000;
+
+/// Parses 1-based page number, optionally written as `#N`, capped at `MAX_LOG_PAGE`
+pub fn parse_page(arg: &str) -> Option<i64> {
+    arg.trim_start_matches('#')
+        .parse::<i64>()
+        .ok()
+        .filter(|page| *page > 0)
+        .map(|page| page.min(MAX_LOG_PAGE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_LOG_PAGE, normalize_login, parse_page};
+
+    #[test]
+    fn parse_page_works() {
+        assert_eq!(parse_page("1"), Some(1));
+        assert_eq!(parse_page("#3"), Some(3));
+        assert_eq!(parse_page("0"), None);
+        assert_eq!(parse_page("-2"), None);
+        assert_eq!(parse_page("two"), None);
+        assert_eq!(parse_page("9223372036854775807"), Some(MAX_LOG_PAGE));
+    }
+
+    #[test]
+    fn normalize_login_works() {
+        assert_eq!(normalize_login("@SomeOne"), "someone");
+        assert_eq!(normalize_login("someone"), "someone");
+    }
 }
@@ -0,0 +1,36 @@
+use crate::config::settings::ModerationFilters;
+
+/// Why the message breaks channel's filters, `None` if it doesn't
+pub fn find_violation(filters: &ModerationFilters, text: &str) -> Option<String> {
+    let lowercase_text = text.to_lowercase();
+
+    if let Some(word) = filters.blocked_words.iter().find(|word| lowercase_text.contains(word.to_lowercase().as_str())) {
+        return Option::Some(format!("blocked word '{}'", word));
+    }
+
+    match filters.max_length {
+        Some(max_length) if text.chars().count() > max_length => Option::Some(format!("longer than {} characters", max_length)),
+        _ => Option::None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::settings::ModerationFilters;
+
+    use super::find_violation;
+
+    #[test]
+    fn find_violation_works() {
+        let filters = ModerationFilters {
+            blocked_words: vec!["Pepega".to_string()],
+            max_length: Option::Some(10),
+            timeout_sec: Option::None,
+        };
+
+        assert_eq!(find_violation(&filters, "what a PEPEGA").as_deref(), Option::Some("blocked word 'Pepega'"));
+        assert_eq!(find_violation(&filters, "LULW LULW LULW").as_deref(), Option::Some("longer than 10 characters"));
+        assert_eq!(find_violation(&filters, "LULW"), Option::None);
+        assert_eq!(find_violation(&ModerationFilters::default(), "what a Pepega"), Option::None);
+    }
+}
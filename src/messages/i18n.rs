@@ -0,0 +1,155 @@
+//! Bot's replies in channel's or chatter's language, catalogs are Fluent files in `locales/`
+
+use std::collections::HashMap;
+
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use fluent_bundle::concurrent::FluentBundle;
+use unic_langid::LanguageIdentifier;
+
+use crate::config::settings::DEFAULT_LOCALE;
+
+/// Catalogs built into the binary, every key of the default one has to be in the rest
+pub const LOCALES: [(&str, &str); 3] = [
+    ("de", include_str!("../../locales/de.ftl")),
+    ("en", include_str!("../../locales/en.ftl")),
+    ("pl", include_str!("../../locales/pl.ftl")),
+];
+
+/// Language part of the locale, e.g. `pl` of `pl-PL`
+fn language_of(locale: &str) -> String {
+    locale.split(['-', '_']).next().unwrap_or(locale).to_lowercase()
+}
+
+/// Shipped locale of the locale's language, e.g. `pl` for `pl-PL`
+pub fn find_locale(locale: &str) -> Option<&'static str> {
+    let language = language_of(locale);
+
+    LOCALES.iter().map(|(name, _)| *name).find(|name| *name == language)
+}
+
+/// Whether replies can be sent in the locale's language
+pub fn is_supported(locale: &str) -> bool {
+    find_locale(locale).is_some()
+}
+
+/// Names of shipped locales for chat replies, e.g. `de, en, pl`
+pub fn locale_names() -> String {
+    LOCALES.iter().map(|(name, _)| *name).collect::<Vec<&str>>().join(", ")
+}
+
+pub struct Localizer {
+    bundles: HashMap<String, FluentBundle<FluentResource>>,
+}
+
+impl Localizer {
+    pub fn new() -> anyhow::Result<Self> {
+        let mut bundles = HashMap::new();
+
+        for (name, source) in LOCALES.iter() {
+            let language: LanguageIdentifier = name.parse()?;
+            let resource = FluentResource::try_new(source.to_string())
+                .map_err(|(_, errors)| anyhow::anyhow!("Failed to parse '{}' catalog: {:?}", name, errors))?;
+
+            let mut bundle = FluentBundle::new_concurrent(vec![language]);
+            // Unicode isolation marks show up as garbage in some chat clients
+            bundle.set_use_isolating(false);
+            bundle.add_resource(resource)
+                .map_err(|errors| anyhow::anyhow!("Failed to load '{}' catalog: {:?}", name, errors))?;
+
+            bundles.insert(name.to_string(), bundle);
+        }
+
+        Ok(Self {
+            bundles,
+        })
+    }
+
+    /// Message in the locale's language, falls back to the default locale and then to the key itself
+    pub fn format(&self, locale: &str, key: &str, args: &[(&str, FluentValue)]) -> String {
+        let bundle = self.bundles.get(language_of(locale).as_str())
+            .filter(|bundle| bundle.has_message(key))
+            .or_else(|| self.bundles.get(DEFAULT_LOCALE));
+
+        let pattern = bundle
+            .and_then(|bundle| bundle.get_message(key))
+            .and_then(|message| message.value());
+
+        let (bundle, pattern) = match (bundle, pattern) {
+            (Some(bundle), Some(pattern)) => (bundle, pattern),
+            _ => {
+                log::warn!("No message '{}' in any catalog", key);
+                return key.to_string();
+            },
+        };
+
+        let mut fluent_args = FluentArgs::new();
+
+        for (name, value) in args {
+            fluent_args.set(*name, value.clone());
+        }
+
+        let mut errors = vec![];
+        let message = bundle.format_pattern(pattern, Option::Some(&fluent_args), &mut errors).to_string();
+
+        if !errors.is_empty() {
+            log::warn!("Failed to format message '{}': {:?}", key, errors);
+        }
+
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fluent_bundle::{FluentResource, FluentValue};
+    use fluent_syntax::ast::Entry;
+
+    use crate::config::settings::DEFAULT_LOCALE;
+
+    use super::{find_locale, is_supported, Localizer, LOCALES};
+
+    fn message_ids(source: &str) -> Vec<String> {
+        let resource = FluentResource::try_new(source.to_string()).unwrap();
+
+        resource.entries()
+            .filter_map(|entry| match entry {
+                Entry::Message(message) => Option::Some(message.id.name.to_string()),
+                _ => Option::None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_key_is_in_every_locale() {
+        let (_, default_source) = LOCALES.iter().find(|(name, _)| *name == DEFAULT_LOCALE).unwrap();
+        let default_ids = message_ids(default_source);
+
+        assert!(!default_ids.is_empty());
+
+        for (name, source) in LOCALES.iter() {
+            let ids = message_ids(source);
+            let missing: Vec<&String> = default_ids.iter().filter(|id| !ids.contains(id)).collect();
+            let unknown: Vec<&String> = ids.iter().filter(|id| !default_ids.contains(id)).collect();
+
+            assert!(missing.is_empty(), "Locale '{}' misses keys: {:?}", name, missing);
+            assert!(unknown.is_empty(), "Locale '{}' has keys the default one doesn't: {:?}", name, unknown);
+        }
+    }
+
+    #[test]
+    fn format_works() {
+        let localizer = Localizer::new().unwrap();
+        let args = [("name", FluentValue::from("Forsen")), ("count", FluentValue::from(3))];
+
+        assert_eq!(localizer.format("en", "hello", &args), "Hello, Forsen!");
+        assert_eq!(localizer.format("pl-PL", "forget-me-deleted", &args), "Forsen, usunięto 3 twoje zapisane wiadomości");
+        assert_eq!(localizer.format("en", "forget-me-deleted", &[("name", FluentValue::from("Forsen")), ("count", FluentValue::from(1))]), "Forsen, deleted your 1 logged message");
+        assert_eq!(localizer.format("en", "forget-me-deleted", &args), "Forsen, deleted all 3 of your logged messages");
+        assert_eq!(localizer.format("ja", "hello", &args), "Hello, Forsen!");
+        assert_eq!(localizer.format("en", "no-such-key", &args), "no-such-key");
+
+        assert_eq!(find_locale("PL-pl"), Option::Some("pl"));
+        assert!(is_supported("de_AT"));
+        assert!(!is_supported("ja"));
+    }
+}
@@ -1,7 +1,8 @@
 use self::processor::MessageProcessor;
 
-static COMMAND_PREFIX: &'static str = "~";
-
 pub mod core;
 pub mod commands;
+pub mod cooldowns;
+pub mod filters;
+pub mod i18n;
 pub mod processor;
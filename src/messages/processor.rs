@@ -1,48 +1,136 @@
 use std::sync::Arc;
+use std::time::Instant;
 
+use fluent_bundle::FluentValue;
 use tokio::sync::RwLock;
 use twitch_irc::message::{PrivmsgMessage, ServerMessage};
 
-use crate::bot::TwitchChatClient;
+use crate::auth::TokenClient;
+use crate::auth::scopes::Feature;
+use crate::bot::{BotContext, TwitchChatClient};
+use crate::config::Config;
+use crate::config::settings::ChannelSettings;
 
-use super::COMMAND_PREFIX;
-use super::core::Command;
+use super::core::{Command, is_moderator};
 use super::commands::hello_command::HelloCommand;
-use sqlx::PgPool;
+use super::cooldowns::CooldownTracker;
+use super::i18n::Localizer;
+use super::filters::find_violation;
 use crate::database::entity::chat_log_message::ChatLogMessage;
+use crate::database::entity::chatter::Chatter;
+use crate::database::repository::Repositories;
 use crate::messages::commands::CommandItem;
-use crate::messages::commands::current_command::CurrentCommand;
+use crate::messages::commands::first_message_command::FirstMessageCommand;
+use crate::messages::commands::forget_me_command::ForgetMeCommand;
+use crate::messages::commands::last_seen_command::LastSeenCommand;
+use crate::messages::commands::logs_command::LogsCommand;
+use crate::messages::commands::search_command::SearchCommand;
+use crate::messages::commands::set_language_command::SetLanguageCommand;
+use crate::messages::commands::set_timezone_command::SetTimezoneCommand;
+use crate::messages::commands::status_command::StatusCommand;
+use crate::messages::commands::time_command::TimeCommand;
+use crate::shutdown::PendingTasks;
+use crate::supervisor::BotSupervisor;
+use crate::twitch::helix::{ban_user, delete_chat_message, is_live};
 
+#[derive(Clone)]
 pub struct MessageProcessor {
+    /// Login the bot speaks as in the channel
+    bot_login: String,
     chat_client: Arc<RwLock<TwitchChatClient>>,
-    commands: Vec<CommandItem>,
-    db_pool: Arc<RwLock<PgPool>>,
+    commands: Arc<Vec<CommandItem>>,
+    config: Arc<RwLock<Config>>,
+    cooldowns: CooldownTracker,
+    /// Additional identity the bot speaks and moderates as, `None` for the default one
+    identity: Option<String>,
+    localizer: Arc<Localizer>,
+    pending: PendingTasks,
+    repositories: Repositories,
+    supervisor: BotSupervisor,
+    token_client: Arc<RwLock<TokenClient>>,
+}
+
+/// Greeting template with `{channel}` and `{bot}` replaced
+fn render_greeting(template: &str, channel: &str, bot_login: &str) -> String {
+    template.replace("{channel}", channel).replace("{bot}", bot_login)
 }
 
 impl MessageProcessor {
-    pub fn new(chat_client: Arc<RwLock<TwitchChatClient>>, db_pool: Arc<RwLock<PgPool>>) -> Self {
-        let commands = MessageProcessor::get_commands();
+    pub fn new(context: &BotContext, bot_login: String, identity: Option<String>, chat_client: Arc<RwLock<TwitchChatClient>>) -> Self {
+        let commands = Arc::new(MessageProcessor::get_commands());
 
         Self {
+            bot_login,
             chat_client,
             commands,
-            db_pool,
+            config: context.config.clone(),
+            cooldowns: context.cooldowns.clone(),
+            identity,
+            localizer: context.localizer.clone(),
+            pending: context.pending.clone(),
+            repositories: context.repositories.clone(),
+            supervisor: context.supervisor.clone(),
+            token_client: context.token_client.clone(),
         }
     }
 
     pub fn get_commands() -> Vec<CommandItem> {
         vec![
             HelloCommand::default(),
-            CurrentCommand::default(),
+            FirstMessageCommand::default(),
+            ForgetMeCommand::default(),
+            LastSeenCommand::default(),
+            LogsCommand::default(),
+            SearchCommand::default(),
+            SetLanguageCommand::default(),
+            SetTimezoneCommand::default(),
+            StatusCommand::default(),
+            TimeCommand::default(),
         ]
     }
 
-    pub fn find_matching_command(&self, message: &PrivmsgMessage) -> Option<&CommandItem> {
+    pub fn get_repositories(&self) -> &Repositories {
+        &self.repositories
+    }
+
+    pub fn get_supervisor(&self) -> &BotSupervisor {
+        &self.supervisor
+    }
+
+    /// Commands' background work, finished before the bot exits
+    pub fn get_pending(&self) -> PendingTasks {
+        self.pending.clone()
+    }
+
+    /// Bot's reply in the locale's language
+    pub fn tr(&self, locale: &str, key: &str, args: &[(&str, FluentValue)]) -> String {
+        self.localizer.format(locale, key, args)
+    }
+
+    /// Effective settings of the channel, read on every message so reloaded config applies right away
+    pub async fn get_settings(&self, channel: &str) -> ChannelSettings {
+        self.config.read().await.app_config.settings_of(channel)
+    }
+
+    /// Channel's settings with the language the chatter saved with `~setlang`, if they did
+    async fn with_chatter_locale(&self, mut settings: ChannelSettings, chatter_login: &str) -> ChannelSettings {
+        match self.repositories.chatters.find_one(chatter_login).await {
+            Ok(Some(Chatter { locale: Some(locale), .. })) => settings.locale = locale,
+            Ok(_) => {},
+            Err(error) => log::error!("Failed to fetch language of '{}': {}", chatter_login, error),
+        }
+
+        settings
+    }
+
+    pub fn find_matching_command(&self, message: &PrivmsgMessage, settings: &ChannelSettings) -> Option<&CommandItem> {
+        let slug = message.message_text.split_whitespace().next()
+            .and_then(|word| word.strip_prefix(settings.prefix.as_str()))?;
+
         for command in self.commands.iter() {
             let command_info = command.get_command_info();
-            let slug_with_prefix = format!("{}{}", COMMAND_PREFIX, command_info.get_slug());
 
-            if message.message_text.starts_with(slug_with_prefix.as_str()) {
+            if command_info.matches(slug) && command_info.get_slugs().any(|name| settings.is_command_enabled(name)) {
                 return Option::Some(command);
             }
         }
@@ -58,12 +146,13 @@ impl MessageProcessor {
             ServerMessage::ClearMsg(_) => {},
             ServerMessage::GlobalUserState(_) => {},
             ServerMessage::HostTarget(_) => {},
-            ServerMessage::Join(message) => {
-                let channel = message.channel_login.clone();
+            // Other chatters' joins only come with membership capability
+            ServerMessage::Join(message) if message.user_login.eq_ignore_ascii_case(self.bot_login.as_str()) => {
                 log::info!("Joined channel '{}'", message.channel_login);
 
-                self.send_privmsg(channel, "Hej".to_string());
+                self.greet(message.channel_login.clone()).await;
             },
+            ServerMessage::Join(_) => {},
             ServerMessage::Notice(x) => {
                 log::info!("NOTICE: {}", x.message_text);
             },
@@ -75,23 +164,36 @@ impl MessageProcessor {
             ServerMessage::Privmsg(message) => {
                 log::info!("<{}>: {}", message.sender.name, message.message_text);
 
-                // TODO: log message to DB
-                // message.server_timestamp
-                async {
-                    let db_pool = self.db_pool.read().await;
-                    let chat_log_message = ChatLogMessage::new(
-                        message.sender.login.clone(),
-                        message.message_text.clone(),
-                        message.server_timestamp.clone()
-                    );
+                let chat_log_message = ChatLogMessage::new(
+                    message.channel_login.clone(),
+                    message.sender.login.clone(),
+                    message.message_text.clone(),
+                    message.server_timestamp
+                );
+
+                self.repositories.chat_logs.insert(chat_log_message).await?;
 
-                    ChatLogMessage::insert(&db_pool, chat_log_message).await
-                }.await?;
+                let settings = self.get_settings(message.channel_login.as_str()).await;
 
-                let command = self.find_matching_command(&message);
-                if command.is_some() {
-                    let command = command.unwrap();
-                    command.execute(self, &message);
+                if !is_moderator(message) {
+                    if let Some(violation) = find_violation(&settings.filters, message.message_text.as_str()) {
+                        self.moderate(message, &settings, violation);
+
+                        return Ok(());
+                    }
+                }
+
+                if let Some(command) = self.find_matching_command(message, &settings) {
+                    let slug = command.get_command_info().get_slug();
+
+                    // Moderators aren't limited by cooldowns
+                    if is_moderator(message) || self.cooldowns.try_use(&settings.cooldowns, message.channel_login.as_str(), slug, message.sender.login.as_str(), Instant::now()) {
+                        let settings = self.with_chatter_locale(settings, message.sender.login.as_str()).await;
+
+                        command.execute(self, message, &settings);
+                    } else {
+                        log::debug!("Command '{}' of '{}' is cooling down", slug, message.sender.login);
+                    }
 
                     return Ok(());
                 }
@@ -104,19 +206,83 @@ impl MessageProcessor {
                 log::info!("USER NOTICE: {}", message.message_text.clone().unwrap_or("none".to_string()));
             },
             ServerMessage::UserState(_) => {},
-            ServerMessage::Whisper(message) => {
-                log::info!("<{}> whispered: {}", message.sender.name, message.message_text);
-            },
             _ => {}
         }
 
         Ok(())
     }
 
+    /// Sends channel's greeting unless it's disabled, was sent recently or the channel is offline and it's only for live ones
+    async fn greet(&self, channel: String) {
+        let settings = self.get_settings(channel.as_str()).await;
+
+        let greeting = match settings.greeting.as_ref() {
+            Some(greeting) => greeting,
+            None => return,
+        };
+
+        if settings.greet_only_live {
+            let helix_url = self.config.read().await.app_config.twitch.helix_url();
+            let app_token = self.token_client.read().await.app_token.clone();
+
+            let is_live = match app_token {
+                Some(app_token) => is_live(helix_url, channel.as_str(), &app_token).await,
+                None => Err(anyhow::anyhow!("no app token")),
+            };
+
+            match is_live {
+                Ok(true) => {},
+                Ok(false) => {
+                    log::debug!("Channel '{}' is offline, not greeting it", channel);
+                    return;
+                },
+                Err(error) => {
+                    log::warn!("Couldn't check if channel '{}' is live, not greeting it: {}", channel, error);
+                    return;
+                },
+            }
+        }
+
+        if !self.cooldowns.try_greet(channel.as_str(), settings.greeting_every_sec, Instant::now()) {
+            log::debug!("Channel '{}' was greeted recently, not greeting it again", channel);
+            return;
+        }
+
+        self.send_privmsg(channel.clone(), render_greeting(greeting, channel.as_str(), self.bot_login.as_str()));
+    }
+
+    /// Deletes the message or times its author out, as channel's filters say, via Helix as the channel's identity
+    fn moderate(&self, message: &PrivmsgMessage, settings: &ChannelSettings, violation: String) {
+        log::info!("Message of '{}' breaks channel's filters: {}", message.sender.login, violation);
+
+        let config = self.config.clone();
+        let identity = self.identity.clone();
+        let timeout_sec = settings.filters.timeout_sec;
+        let token_client = self.token_client.clone();
+        let message = message.clone();
+
+        self.pending.spawn(async move {
+            let helix_url = config.read().await.app_config.twitch.helix_url();
+            let token = token_client.read().await.token_for(message.channel_login.as_str(), identity.as_deref(), Feature::Moderation);
+
+            let result = match (token, timeout_sec) {
+                (Err(error), _) => Err(error),
+                (Ok(token), Some(timeout_sec)) => {
+                    ban_user(helix_url, &token, message.channel_id.as_str(), message.sender.id.as_str(), Option::Some(timeout_sec), violation.as_str()).await
+                },
+                (Ok(token), None) => delete_chat_message(helix_url, &token, message.channel_id.as_str(), message.message_id.as_str()).await,
+            };
+
+            if let Err(error) = result {
+                log::error!("Failed to moderate message of '{}' in channel '{}': {}", message.sender.login, message.channel_login, error);
+            }
+        });
+    }
+
     pub fn send_privmsg(&self, channel: String, message: String) {
         let client = self.chat_client.clone();
 
-        tokio::spawn(async move {
+        self.pending.spawn(async move {
             let channel = channel.clone();
             let client = client.read().await;
             let message = message.clone();
@@ -131,3 +297,14 @@ impl MessageProcessor {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::render_greeting;
+
+    #[test]
+    fn render_greeting_works() {
+        assert_eq!(render_greeting("Hej {channel}, {bot} is here", "pepega", "develbot"), "Hej pepega, develbot is here");
+        assert_eq!(render_greeting("Hej", "pepega", "develbot"), "Hej");
+    }
+}
@@ -0,0 +1,138 @@
+//! Graceful shutdown on SIGINT/SIGTERM, work in flight is finished within `global.shutdown_timeout_sec`
+
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use crate::bot::BotContext;
+
+pub const DEFAULT_SHUTDOWN_TIMEOUT_SEC: u64 = 10;
+
+/// Tasks the bot has to wait for before it exits: bots' message loops, outgoing messages and database writes
+#[derive(Clone, Default)]
+pub struct PendingTasks {
+    count: Arc<AtomicUsize>,
+    finished: Arc<Notify>,
+}
+
+/// Counts the task as finished even if it panics
+pub struct PendingGuard(PendingTasks);
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.finished.notify_waiters();
+        }
+    }
+}
+
+impl PendingTasks {
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<()> where F: Future<Output = ()> + Send + 'static {
+        let guard = self.track();
+
+        tokio::spawn(async move {
+            let _guard = guard;
+
+            future.await;
+        })
+    }
+
+    /// Counts work of a long-running task as pending until the guard is dropped
+    pub fn track(&self) -> PendingGuard {
+        self.count.fetch_add(1, Ordering::SeqCst);
+
+        PendingGuard(self.clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once every spawned task is finished
+    pub async fn wait(&self) {
+        loop {
+            // Registered before checking, so a task finishing in between isn't missed
+            let finished = self.finished.notified();
+
+            if self.len() == 0 {
+                return;
+            }
+
+            finished.await;
+        }
+    }
+}
+
+/// Name of the signal the bot was asked to stop with
+pub async fn wait_for_signal() -> anyhow::Result<&'static str> {
+    let mut interrupts = signal(SignalKind::interrupt())?;
+    let mut terminations = signal(SignalKind::terminate())?;
+
+    let name = tokio::select! {
+        _ = interrupts.recv() => "SIGINT",
+        _ = terminations.recv() => "SIGTERM",
+    };
+
+    Ok(name)
+}
+
+/// Stops taking new messages, lets bots finish what they're doing, parts channels and closes connections,
+/// fails if that takes longer than the configured deadline
+pub async fn shut_down(context: &BotContext) -> anyhow::Result<()> {
+    let timeout_sec = context.config.read().await.app_config.global.shutdown_timeout_sec.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SEC);
+
+    let result = tokio::time::timeout(Duration::from_secs(timeout_sec), async {
+        context.shutdown.cancel();
+
+        log::info!("Waiting for {} pending tasks", context.pending.len());
+        context.pending.wait().await;
+
+        context.chat_connections.write().await.part_all().await;
+        log::info!("Parted every channel");
+
+        context.token_client.write().await.stop();
+
+        context.repositories.close().await;
+        log::info!("Closed database connections");
+    }).await;
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(_) => Err(anyhow::anyhow!("Shutdown didn't finish within {} seconds, {} tasks were still pending", timeout_sec, context.pending.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::PendingTasks;
+
+    #[tokio::test]
+    async fn pending_tasks_are_awaited() {
+        let pending = PendingTasks::default();
+
+        pending.wait().await;
+
+        let (sender, receiver) = tokio::sync::oneshot::channel::<()>();
+
+        pending.spawn(async move {
+            let _ = receiver.await;
+        });
+        pending.spawn(async {
+            panic!("panicking tasks are finished too");
+        });
+
+        assert!(tokio::time::timeout(Duration::from_millis(50), pending.wait()).await.is_err());
+
+        sender.send(()).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(1), pending.wait()).await.unwrap();
+        assert_eq!(pending.len(), 0);
+    }
+}
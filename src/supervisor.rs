@@ -0,0 +1,178 @@
+//! Keeps channels' bots running, failed ones are restarted with exponential backoff
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio_util::sync::CancellationToken;
+
+use crate::auth::checker::Backoff;
+
+const MIN_BACKOFF_SEC: u64 = 1;
+const MAX_BACKOFF_SEC: u64 = 300;
+/// Bots that ran that long before failing start over from the shortest backoff
+pub const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BotState {
+    Starting,
+    Running,
+    /// Failed `attempt` times in a row, started again in `backoff_sec`
+    Restarting { attempt: u32, backoff_sec: u64, error: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct BotStatus {
+    pub state: BotState,
+    /// When the bot got into its current state
+    pub since: DateTime<Utc>,
+    /// Failures since the channel was joined
+    pub restarts: u32,
+    /// When the bot last received anything from chat
+    pub last_message_at: Option<DateTime<Utc>>,
+}
+
+struct SupervisedBot {
+    status: BotStatus,
+    /// Cancelled when the channel is parted, bot isn't restarted afterwards
+    stop: CancellationToken,
+}
+
+/// States of every channel's bot, shared by bots, commands and status endpoints
+#[derive(Clone, Default)]
+pub struct BotSupervisor {
+    bots: Arc<Mutex<HashMap<String, SupervisedBot>>>,
+}
+
+/// Wait before restarts of a bot, doubled every time up to 5 minutes, each supervised bot has its own
+pub fn restart_backoff() -> Backoff {
+    Backoff::new(Duration::from_secs(MIN_BACKOFF_SEC), Duration::from_secs(MAX_BACKOFF_SEC))
+}
+
+impl BotSupervisor {
+    /// Starts tracking channel's bot, returns the token stopping it, cancelled on shutdown too
+    pub fn register(&self, channel: &str, shutdown: &CancellationToken) -> CancellationToken {
+        let stop = shutdown.child_token();
+        let status = BotStatus {
+            state: BotState::Starting,
+            since: Utc::now(),
+            restarts: 0,
+            last_message_at: Option::None,
+        };
+
+        let previous = self.bots.lock().unwrap()
+            .insert(channel.to_lowercase(), SupervisedBot { status, stop: stop.clone() });
+
+        // Only one bot per channel, e.g. when the channel moves to another identity
+        if let Some(previous) = previous {
+            previous.stop.cancel();
+        }
+
+        stop
+    }
+
+    /// Stops channel's bot for good and forgets it
+    pub fn unregister(&self, channel: &str) {
+        if let Some(bot) = self.bots.lock().unwrap().remove(channel.to_lowercase().as_str()) {
+            bot.stop.cancel();
+        }
+    }
+
+    pub fn set_state(&self, channel: &str, state: BotState) {
+        let mut bots = self.bots.lock().unwrap();
+
+        let bot = match bots.get_mut(channel.to_lowercase().as_str()) {
+            Some(bot) => bot,
+            None => return,
+        };
+
+        match &state {
+            BotState::Starting => log::info!("Starting bot for channel '{}'", channel),
+            BotState::Running => log::info!("Bot for channel '{}' is running", channel),
+            BotState::Restarting { attempt, backoff_sec, error } => {
+                log::error!("Bot for channel '{}' failed {} times in a row, restarting in {}s: {}", channel, attempt, backoff_sec, error);
+                bot.status.restarts += 1;
+            },
+        }
+
+        bot.status.state = state;
+        bot.status.since = Utc::now();
+    }
+
+    pub fn record_message(&self, channel: &str) {
+        if let Some(bot) = self.bots.lock().unwrap().get_mut(channel.to_lowercase().as_str()) {
+            bot.status.last_message_at = Option::Some(Utc::now());
+        }
+    }
+
+    /// Status of the channel's bot, `None` if it isn't supervised
+    pub fn status_of(&self, channel: &str) -> Option<BotStatus> {
+        self.bots.lock().unwrap().get(channel.to_lowercase().as_str()).map(|bot| bot.status.clone())
+    }
+
+    /// Statuses of every channel's bot, ordered by channel
+    pub fn statuses(&self) -> Vec<(String, BotStatus)> {
+        let mut statuses: Vec<(String, BotStatus)> = self.bots.lock().unwrap().iter()
+            .map(|(channel, bot)| (channel.clone(), bot.status.clone()))
+            .collect();
+
+        statuses.sort_by(|(left, _), (right, _)| left.cmp(right));
+
+        statuses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_util::sync::CancellationToken;
+
+    use super::{BotState, BotSupervisor, restart_backoff};
+
+    #[test]
+    fn restart_backoff_works() {
+        let mut backoff = restart_backoff();
+        let delays: Vec<u64> = (0..10).map(|_| backoff.next_delay().as_secs()).collect();
+
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 64, 128, 256, 300]);
+        assert_eq!(backoff.attempts(), 10);
+    }
+
+    #[test]
+    fn supervisor_tracks_bots() {
+        let shutdown = CancellationToken::new();
+        let supervisor = BotSupervisor::default();
+
+        let pepega = supervisor.register("Pepega", &shutdown);
+        let forsen = supervisor.register("forsen", &shutdown);
+
+        supervisor.set_state("pepega", BotState::Restarting { attempt: 1, backoff_sec: 1, error: "no token".to_string() });
+        supervisor.set_state("forsen", BotState::Running);
+        supervisor.record_message("Forsen");
+
+        let statuses = supervisor.statuses();
+
+        assert_eq!(statuses[0].0, "forsen");
+        assert_eq!(statuses[0].1.state, BotState::Running);
+        assert!(statuses[0].1.last_message_at.is_some());
+        assert!(statuses[1].1.last_message_at.is_none());
+        assert_eq!(statuses[1].1.restarts, 1);
+        assert_eq!(supervisor.status_of("FORSEN").map(|status| status.state), Option::Some(BotState::Running));
+        assert!(supervisor.status_of("xqc").is_none());
+
+        // Joining the channel again replaces its bot
+        let pepega_again = supervisor.register("pepega", &shutdown);
+
+        assert!(pepega.is_cancelled());
+        assert_eq!(supervisor.statuses()[1].1.state, BotState::Starting);
+
+        supervisor.unregister("pepega");
+
+        assert!(pepega_again.is_cancelled());
+        assert_eq!(supervisor.statuses().len(), 1);
+
+        shutdown.cancel();
+
+        assert!(forsen.is_cancelled());
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::RwLock;
+use twitch_irc::ClientConfig;
+use twitch_irc::message::ServerMessage;
+
+use crate::auth::{TokenClient, UserTokenCredentials};
+use crate::bot::TwitchChatClient;
+
+/// Connection's channels that aren't parted by then are left as they are
+const PART_TIMEOUT: Duration = Duration::from_secs(5);
+
+type ChannelSenders = Arc<RwLock<HashMap<String, UnboundedSender<ServerMessage>>>>;
+
+/// Chat connection of one identity, shared by every channel mapped to it
+struct ChatConnection {
+    chat_client: Arc<RwLock<TwitchChatClient>>,
+    channels: ChannelSenders,
+}
+
+/// Keeps one chat connection per bot identity and routes incoming messages to channels' bots
+pub struct ChatConnections {
+    /// Keyed by identity login, `None` is the default identity
+    connections: HashMap<Option<String>, ChatConnection>,
+    token_client: Arc<RwLock<TokenClient>>,
+}
+
+/// Channel login the message was sent to, `None` for messages not bound to a channel, like whispers
+fn channel_of(message: &ServerMessage) -> Option<String> {
+    message.source().params.first()
+        .and_then(|param| param.strip_prefix('#'))
+        .filter(|channel| !channel.is_empty())
+        .map(|channel| channel.to_lowercase())
+}
+
+/// Messages not bound to a channel don't belong to any channel's bot, so they're only logged
+fn log_unbound(message: &ServerMessage) {
+    match message {
+        ServerMessage::Notice(message) => log::info!("NOTICE: {}", message.message_text),
+        ServerMessage::Whisper(message) => log::info!("<{}> whispered: {}", message.sender.name, message.message_text),
+        _ => {},
+    }
+}
+
+async fn route_messages(mut incoming_messages: UnboundedReceiver<ServerMessage>, channels: ChannelSenders) {
+    while let Some(message) = incoming_messages.recv().await {
+        let channels = channels.read().await;
+
+        let channel = match channel_of(&message) {
+            Some(channel) => channel,
+            None => {
+                log_unbound(&message);
+                continue;
+            },
+        };
+
+        if let Some(sender) = channels.get(channel.as_str()) {
+            // Channel's bot is gone, nothing to do about its messages
+            let _ = sender.send(message);
+        }
+    }
+}
+
+impl ChatConnections {
+    pub fn new(token_client: Arc<RwLock<TokenClient>>) -> Self {
+        Self {
+            connections: HashMap::new(),
+            token_client,
+        }
+    }
+
+    async fn connect(&self, identity: Option<String>) -> anyhow::Result<ChatConnection> {
+        if self.token_client.read().await.identity_token(identity.as_deref()).is_none() {
+            return Err(anyhow::anyhow!(
+                "Can't start chat processor, no user token of identity '{}', is it listed in twitch.identities?",
+                identity.as_deref().unwrap_or("default")
+            ));
+        }
+
+        let credentials = UserTokenCredentials::new(self.token_client.clone(), identity);
+        let (incoming_messages, chat_client) = TwitchChatClient::new(ClientConfig::new_simple(credentials));
+        let channels: ChannelSenders = Arc::new(RwLock::new(HashMap::new()));
+
+        tokio::spawn(route_messages(incoming_messages, channels.clone()));
+
+        Ok(ChatConnection {
+            chat_client: Arc::new(RwLock::new(chat_client)),
+            channels,
+        })
+    }
+
+    /// Chat client of the identity and messages of the channel, the identity connects on first use
+    pub async fn register_channel(&mut self, identity: Option<String>, channel: &str)
+        -> anyhow::Result<(Arc<RwLock<TwitchChatClient>>, UnboundedReceiver<ServerMessage>)>
+    {
+        if !self.connections.contains_key(&identity) {
+            log::debug!("Connecting to chat as identity '{}'", identity.as_deref().unwrap_or("default"));
+
+            let connection = self.connect(identity.clone()).await?;
+            self.connections.insert(identity.clone(), connection);
+        }
+
+        let connection = &self.connections[&identity];
+        let (sender, receiver) = unbounded_channel();
+
+        connection.channels.write().await.insert(channel.to_lowercase(), sender);
+
+        Ok((connection.chat_client.clone(), receiver))
+    }
+
+    /// Parts the channel and stops routing its messages, so its bot's message loop ends
+    pub async fn unregister_channel(&mut self, identity: Option<String>, channel: &str) {
+        if let Some(connection) = self.connections.get(&identity) {
+            let channel = channel.to_lowercase();
+
+            connection.channels.write().await.remove(channel.as_str());
+            connection.chat_client.read().await.part(channel);
+        }
+    }
+
+    /// Whether the server confirmed joining each registered channel
+    pub async fn joined_channels(&self) -> HashMap<String, bool> {
+        let mut joined = HashMap::new();
+
+        for connection in self.connections.values() {
+            let channels: Vec<String> = connection.channels.read().await.keys().cloned().collect();
+            let chat_client = connection.chat_client.read().await;
+
+            for channel in channels {
+                let (_, is_joined) = chat_client.get_channel_status(channel.clone()).await;
+
+                joined.insert(channel, is_joined);
+            }
+        }
+
+        joined
+    }
+
+    /// Parts every channel and waits up to `PART_TIMEOUT` per connection for the server to confirm it, so nothing is left unsent
+    pub async fn part_all(&mut self) {
+        for connection in self.connections.values() {
+            let channels: Vec<String> = connection.channels.write().await.drain().map(|(channel, _)| channel).collect();
+            let chat_client = connection.chat_client.read().await;
+
+            for channel in channels.iter() {
+                chat_client.part(channel.clone());
+            }
+
+            let parted = tokio::time::timeout(PART_TIMEOUT, async {
+                for channel in channels {
+                    while chat_client.get_channel_status(channel.clone()).await.1 {
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                    }
+                }
+            }).await;
+
+            if parted.is_err() {
+                log::warn!("Twitch didn't confirm parting channels within {}s", PART_TIMEOUT.as_secs());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use twitch_irc::message::{IRCMessage, ServerMessage};
+
+    use super::channel_of;
+
+    fn server_message(source: &str) -> ServerMessage {
+        ServerMessage::try_from(IRCMessage::parse(source).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn channel_of_works() {
+        assert_eq!(channel_of(&server_message("@badge-info=;badges=;color=;display-name=Forsen;emotes=;flags=;id=1;mod=0;room-id=1;subscriber=0;tmi-sent-ts=1577040814959;turbo=0;user-id=2;user-type= :forsen!forsen@forsen.tmi.twitch.tv PRIVMSG #Pepega :LULW")).as_deref(), Option::Some("pepega"));
+        assert_eq!(channel_of(&server_message(":develbot!develbot@develbot.tmi.twitch.tv JOIN #pepega")).as_deref(), Option::Some("pepega"));
+        assert_eq!(channel_of(&server_message("@badges=;color=;display-name=Forsen;emotes=;message-id=1;thread-id=1_2;turbo=0;user-id=1;user-type= :forsen!forsen@forsen.tmi.twitch.tv WHISPER develbot :hi")), Option::None);
+    }
+}
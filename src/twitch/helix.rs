@@ -0,0 +1,167 @@
+use reqwest::Method;
+use serde_json::json;
+use twitch_api2::{HttpClient, TWITCH_HELIX_URL};
+use twitch_api2::client::{BoxedFuture, Req, Response};
+use twitch_api2::helix::HelixClient;
+use twitch_api2::helix::streams::GetStreamsRequest;
+use twitch_oauth2::TwitchToken;
+
+use crate::auth::http_client::rebase_url;
+
+/// `reqwest` client for `twitch_api2` that sends Helix requests to `base_url` instead of Twitch
+#[derive(Clone, Debug)]
+pub struct HelixHttpClient {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl HelixHttpClient {
+    pub fn new(base_url: String) -> Self {
+        Self {
+            base_url,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+impl<'a> HttpClient<'a> for HelixHttpClient {
+    type Error = reqwest::Error;
+
+    fn req(&'a self, mut request: Req) -> BoxedFuture<'a, Result<Response, Self::Error>> {
+        let uri = rebase_url(request.uri().to_string().as_str(), TWITCH_HELIX_URL, self.base_url.as_str())
+            .and_then(|uri| uri.parse().ok());
+
+        if let Some(uri) = uri {
+            *request.uri_mut() = uri;
+        }
+
+        self.client.req(request)
+    }
+}
+
+/// Whether the channel is streaming right now
+pub async fn is_live<T: TwitchToken>(helix_url: String, channel: &str, token: &T) -> anyhow::Result<bool> {
+    let client: HelixClient<HelixHttpClient> = HelixClient::with_client(HelixHttpClient::new(helix_url));
+    let request = GetStreamsRequest::builder()
+        .user_login(vec![channel.into()])
+        .build();
+
+    let response = client.req_get(request, token).await?;
+
+    Ok(!response.data.is_empty())
+}
+
+/// Sends a Helix request the `twitch_api2` version in use has no type for, fails unless Twitch answers with success
+async fn send_helix<T: TwitchToken>(
+    helix_url: &str,
+    method: Method,
+    path: &str,
+    query: &[(&str, &str)],
+    body: Option<serde_json::Value>,
+    token: &T,
+) -> anyhow::Result<()> {
+    let url = format!("{}/{}", helix_url.trim_end_matches('/'), path);
+    let mut request = reqwest::Client::new()
+        .request(method, url.as_str())
+        .query(query)
+        .header("Client-Id", token.client_id().as_str())
+        .bearer_auth(token.token().secret());
+
+    if let Some(body) = body {
+        request = request
+            .header("Content-Type", "application/json")
+            .body(body.to_string());
+    }
+
+    let response = request.send().await?;
+    let status = response.status();
+
+    if !status.is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(anyhow::anyhow!("Helix answered {} to {}: {}", status, path, message));
+    }
+
+    Ok(())
+}
+
+/// Moderator's user ID, the token has to belong to a moderator of the channel
+fn moderator_id<T: TwitchToken>(token: &T) -> anyhow::Result<&str> {
+    token.user_id()
+        .filter(|user_id| !user_id.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Token has no user ID, it can't moderate"))
+}
+
+/// Bans the user from the channel, or times them out for `duration_sec` if given
+pub async fn ban_user<T: TwitchToken>(
+    helix_url: String,
+    token: &T,
+    broadcaster_id: &str,
+    user_id: &str,
+    duration_sec: Option<u64>,
+    reason: &str,
+) -> anyhow::Result<()> {
+    let mut data = json!({ "user_id": user_id, "reason": reason });
+
+    if let Some(duration_sec) = duration_sec {
+        data["duration"] = json!(duration_sec);
+    }
+
+    let query = [("broadcaster_id", broadcaster_id), ("moderator_id", moderator_id(token)?)];
+
+    send_helix(helix_url.as_str(), Method::POST, "moderation/bans", &query, Option::Some(json!({ "data": data })), token).await
+}
+
+/// Deletes a single chat message
+pub async fn delete_chat_message<T: TwitchToken>(helix_url: String, token: &T, broadcaster_id: &str, message_id: &str) -> anyhow::Result<()> {
+    let query = [("broadcaster_id", broadcaster_id), ("moderator_id", moderator_id(token)?), ("message_id", message_id)];
+
+    send_helix(helix_url.as_str(), Method::DELETE, "moderation/chat", &query, Option::None, token).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use twitch_oauth2::{AccessToken, ClientId, UserToken};
+
+    use crate::auth::mock_oauth::MockOAuthServer;
+
+    use super::{ban_user, delete_chat_message};
+
+    #[tokio::test]
+    async fn moderation_uses_helix() {
+        let server = MockOAuthServer::start_at("/helix", Arc::new(|request| {
+            match request.path.as_str() {
+                "/helix/moderation/bans" => (200, r#"{"data":[]}"#.to_string()),
+                "/helix/moderation/chat" => (204, String::new()),
+                _ => (404, String::new()),
+            }
+        })).await;
+
+        let token = UserToken::from_existing_unchecked(
+            AccessToken::new("moderator-token".to_string()),
+            Option::None,
+            ClientId::new("client-id".to_string()),
+            Option::None,
+            "develbot".to_string(),
+            "1234".to_string(),
+            Option::None,
+            Option::None
+        );
+
+        ban_user(server.base_url.clone(), &token, "42", "666", Option::Some(60), "blocked word").await.unwrap();
+        delete_chat_message(server.base_url.clone(), &token, "42", "message-id").await.unwrap();
+
+        let requests = server.requests.lock().unwrap().clone();
+
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/helix/moderation/bans");
+        assert_eq!(requests[0].authorization.as_deref(), Option::Some("Bearer moderator-token"));
+        assert_eq!(requests[0].body, r#"{"data":{"duration":60,"reason":"blocked word","user_id":"666"}}"#);
+        assert_eq!(requests[1].method, "DELETE");
+        assert_eq!(requests[1].path, "/helix/moderation/chat");
+
+        assert!(ban_user(server.base_url.clone(), &token, "42", "666", Option::None, "").await.is_ok());
+        assert!(delete_chat_message(format!("{}/missing", server.base_url), &token, "42", "message-id").await.is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt::{Debug, Formatter};
+
+use async_trait::async_trait;
+use async_tungstenite::tokio::connect_async;
+use async_tungstenite::tungstenite::{Error as WSError, Message as WSMessage};
+use futures::{future, SinkExt, stream, StreamExt, TryStreamExt};
+use futures::sink::Sink;
+use futures::stream::FusedStream;
+use itertools::Either;
+use once_cell::sync::OnceCell;
+use twitch_irc::message::{AsRawIRC, IRCMessage, IRCParseError};
+use twitch_irc::Transport;
+
+pub const TWITCH_IRC_URL: &str = "wss://irc-ws.chat.twitch.tv";
+
+/// `Transport::new` takes no arguments, so the URL of `twitch.urls.irc` is set once on start
+static IRC_URL: OnceCell<String> = OnceCell::new();
+
+/// Points chat connections at given WebSocket URL, only the first call has an effect
+pub fn init_irc_url(url: String) {
+    if IRC_URL.set(url).is_err() {
+        log::warn!("Chat URL is already set, changes of twitch.urls.irc need a restart");
+    }
+}
+
+/// Same as twitch-irc's `WSSTransport`, except it connects to the URL set with `init_irc_url`
+pub struct IrcTransport {
+    incoming_messages: <Self as Transport>::Incoming,
+    outgoing_messages: <Self as Transport>::Outgoing,
+}
+
+impl IrcTransport {
+    /// Connects to given WebSocket URL
+    // Error type is dictated by `Transport`
+    #[allow(clippy::result_large_err)]
+    pub async fn connect(url: &str) -> Result<IrcTransport, WSError> {
+        let (ws_stream, _response) = connect_async(url).await?;
+        let (write_half, read_half) = ws_stream.split();
+
+        let message_stream = read_half
+            .map_err(Either::Left)
+            .try_filter_map(|ws_message| {
+                future::ready(Ok::<_, Either<WSError, IRCParseError>>(match ws_message {
+                    // One WebSocket message can carry multiple IRC messages separated by newlines
+                    WSMessage::Text(text) => Option::Some(stream::iter(
+                        text.lines().map(|line| Ok(line.to_string())).collect::<Vec<Result<String, _>>>()
+                    )),
+                    _ => Option::None,
+                }))
+            })
+            .try_flatten()
+            .try_filter(|line| future::ready(!line.is_empty()))
+            .and_then(|line| future::ready(IRCMessage::parse(&line).map_err(Either::Right)))
+            .fuse();
+
+        let message_sink = write_half
+            .with(|message: IRCMessage| future::ready(Ok(WSMessage::Text(message.as_raw_irc()))));
+
+        Ok(IrcTransport {
+            incoming_messages: Box::new(message_stream),
+            outgoing_messages: Box::new(message_sink),
+        })
+    }
+}
+
+#[async_trait]
+impl Transport for IrcTransport {
+    type ConnectError = WSError;
+    type IncomingError = WSError;
+    type OutgoingError = WSError;
+
+    type Incoming = Box<dyn FusedStream<Item = Result<IRCMessage, Either<WSError, IRCParseError>>> + Unpin + Send + Sync>;
+    type Outgoing = Box<dyn Sink<IRCMessage, Error = Self::OutgoingError> + Unpin + Send + Sync>;
+
+    // Error type is dictated by `Transport`
+    #[allow(clippy::result_large_err)]
+    async fn new() -> Result<IrcTransport, WSError> {
+        IrcTransport::connect(IRC_URL.get().map(String::as_str).unwrap_or(TWITCH_IRC_URL)).await
+    }
+
+    fn split(self) -> (Self::Incoming, Self::Outgoing) {
+        (self.incoming_messages, self.outgoing_messages)
+    }
+}
+
+impl Debug for IrcTransport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IrcTransport").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_tungstenite::tokio::accept_async;
+    use async_tungstenite::tungstenite::Message as WSMessage;
+    use futures::{SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+    use twitch_irc::message::IRCMessage;
+    use twitch_irc::Transport;
+
+    use super::IrcTransport;
+
+    #[tokio::test]
+    async fn transport_connects_to_configured_url() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws_stream = accept_async(stream).await.unwrap();
+
+            ws_stream.send(WSMessage::Text("PING :tmi.twitch.tv\r\n:tmi.twitch.tv 001 develbot :Welcome, GLHF!".to_string())).await.unwrap();
+
+            ws_stream.next().await.unwrap().unwrap()
+        });
+
+        let (mut incoming, mut outgoing) = IrcTransport::connect(url.as_str()).await.unwrap().split();
+
+        assert_eq!(incoming.next().await.unwrap().unwrap().command, "PING");
+        assert_eq!(incoming.next().await.unwrap().unwrap().command, "001");
+
+        outgoing.send(IRCMessage::parse("PONG :tmi.twitch.tv").unwrap()).await.unwrap();
+        assert_eq!(server.await.unwrap(), WSMessage::Text("PONG tmi.twitch.tv".to_string()));
+    }
+}
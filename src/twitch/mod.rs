@@ -0,0 +1,5 @@
+//! Clients for Twitch APIs that can be pointed somewhere else than Twitch, e.g. at Twitch CLI's mock API
+
+pub mod chat;
+pub mod helix;
+pub mod irc;